@@ -1,17 +1,22 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use elevator_system::simulations::concurrency_elevator_system::concurrency_elevator_system;
+use elevator_system::scenario::Scenario;
+use elevator_system::simulations::concurrency_elevator_system;
 use elevator_system::simulations::scheduling_elevator_system::scheduling_elevator_system;
+use elevator_system::SimulationConfig;
 
 fn elevator_system_concurrency(c: &mut Criterion) {
-    // concurrency_elevator_system();
-    c.bench_function("Scheduling Elevator System", |b| {
-        b.iter(|| concurrency_elevator_system());
+    let config = SimulationConfig::from_env();
+    let scenario = Scenario::default();
+    c.bench_function("Concurrency Elevator System", |b| {
+        b.iter(|| concurrency_elevator_system::elevator_system(&config, &scenario));
     });
 }
 fn elevator_system_scheduling(c: &mut Criterion) {
     // scheduling_elevator_system();
+    let config = SimulationConfig::from_env();
+    let scenario = Scenario::default();
     c.bench_function("Scheduling Elevator System", |b| {
-        b.iter(|| scheduling_elevator_system());
+        b.iter(|| scheduling_elevator_system(&config, &scenario));
     });
 }
 
@@ -0,0 +1,101 @@
+use crate::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The up or down button on a landing. Everyone waiting there to go the
+/// same way shares one call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HallCall {
+    pub floor: usize,
+    pub direction: Direction,
+}
+
+/// A floor button inside one car.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CarCall {
+    pub car: String,
+    pub floor: usize,
+}
+
+/// Lamps of every hall and car button in the building.
+///
+/// A hall lamp lights when the first person presses the button and goes out
+/// once a car has taken everyone waiting behind it. A car lamp goes out when
+/// the car opens its doors at that floor.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CallLamps {
+    hall: BTreeMap<HallCall, Vec<usize>>,
+    car: BTreeSet<CarCall>,
+}
+
+impl CallLamps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `person_id` presses `call`. Returns true if this lit the lamp.
+    pub fn press_hall(&mut self, call: HallCall, person_id: usize) -> bool {
+        let waiting = self.hall.entry(call).or_default();
+        if !waiting.contains(&person_id) {
+            waiting.push(person_id);
+        }
+        waiting.len() == 1
+    }
+
+    /// A car picked up `person_id` from `call`. Returns true if this put the
+    /// lamp out, i.e. nobody is left waiting behind the call.
+    pub fn answer_hall(&mut self, call: HallCall, person_id: usize) -> bool {
+        let Some(waiting) = self.hall.get_mut(&call) else {
+            return false;
+        };
+        waiting.retain(|id| *id != person_id);
+        if waiting.is_empty() {
+            self.hall.remove(&call);
+            return true;
+        }
+        false
+    }
+
+    /// Returns true if this lit the lamp.
+    pub fn press_car(&mut self, call: CarCall) -> bool {
+        self.car.insert(call)
+    }
+
+    /// Returns true if the lamp was lit.
+    pub fn answer_car(&mut self, call: &CarCall) -> bool {
+        self.car.remove(call)
+    }
+
+    /// Put every lamp out, returning the calls that were lit.
+    pub fn clear(&mut self) -> (Vec<HallCall>, Vec<CarCall>) {
+        let hall = std::mem::take(&mut self.hall).into_keys().collect();
+        let car = std::mem::take(&mut self.car).into_iter().collect();
+        (hall, car)
+    }
+
+    pub fn hall_lit(&self, call: HallCall) -> bool {
+        self.hall.contains_key(&call)
+    }
+
+    pub fn car_lit(&self, call: &CarCall) -> bool {
+        self.car.contains(call)
+    }
+
+    /// People waiting behind `call`, in the order they pressed it.
+    pub fn waiting(&self, call: HallCall) -> &[usize] {
+        self.hall.get(&call).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Lit hall calls, from the lowest floor up.
+    pub fn hall_calls(&self) -> impl Iterator<Item = HallCall> + '_ {
+        self.hall.keys().copied()
+    }
+
+    /// Lit car calls of car `car`, from the lowest floor up.
+    pub fn car_calls<'a>(&'a self, car: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.car
+            .iter()
+            .filter(move |call| call.car == car)
+            .map(|call| call.floor)
+    }
+}
@@ -0,0 +1,440 @@
+use crate::calls::{CarCall, HallCall};
+use crate::faults::Fault;
+use crate::{lock, ButtonPressed, Direction, ElevatorError};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Something observable that happened during a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SimEvent {
+    CallRegistered {
+        request: ButtonPressed,
+    },
+    /// The call was turned away at intake.
+    CallRejected {
+        request: ButtonPressed,
+        reason: String,
+    },
+    /// The call was dropped before a car served it, as on a fire recall.
+    CallCancelled {
+        request: ButtonPressed,
+    },
+    /// The first person waiting behind `call` pressed it.
+    HallCallLit {
+        call: HallCall,
+    },
+    /// A car took everyone waiting behind `call`.
+    HallCallCancelled {
+        call: HallCall,
+    },
+    CarCallLit {
+        call: CarCall,
+    },
+    /// The car opened its doors at the floor of `call`.
+    CarCallCancelled {
+        call: CarCall,
+    },
+    CarAssigned {
+        elevator_id: String,
+        person_id: usize,
+    },
+    /// No single car serves the whole trip, so the person changes cars at `floor`.
+    TransferPlanned {
+        person_id: usize,
+        floor: usize,
+    },
+    /// A car took a batch of calls from its queue.
+    RequestsTaken {
+        elevator_id: String,
+        person_ids: Vec<usize>,
+    },
+    /// `direction` is `None` when the car stopped where it already was.
+    CarArrived {
+        elevator_id: String,
+        floor: usize,
+        direction: Option<Direction>,
+    },
+    DoorsOpened {
+        elevator_id: String,
+        floor: usize,
+    },
+    PassengerBoarded {
+        elevator_id: String,
+        person_id: usize,
+        floor: usize,
+    },
+    PassengerAlighted {
+        elevator_id: String,
+        person_id: usize,
+        floor: usize,
+    },
+    /// The car had no room for `person_ids`, who were put back in the hall queue.
+    CarFull {
+        elevator_id: String,
+        floor: usize,
+        person_ids: Vec<usize>,
+    },
+    DoorsClosed {
+        elevator_id: String,
+        floor: usize,
+    },
+    CarIdle {
+        elevator_id: String,
+    },
+    /// The car's worker finished; it takes no more calls this run.
+    CarStopped {
+        elevator_id: String,
+    },
+    MaintenanceStarted {
+        elevator_id: String,
+    },
+    /// The car stopped at `floor` on its way out of service and let
+    /// `person_ids` out, to carry on in another car.
+    CarEvacuated {
+        elevator_id: String,
+        floor: usize,
+        person_ids: Vec<usize>,
+    },
+    ReturnedToService {
+        elevator_id: String,
+    },
+    /// Mains power lost: the cars run on battery to a floor and wait there.
+    PowerOutage,
+    PowerRestored,
+    /// Fire service Phase I: every call is cancelled and the cars go
+    /// non-stop to `floor`.
+    FireRecall {
+        floor: usize,
+    },
+    /// The car reached the recall floor and parked with its doors open.
+    CarRecalled {
+        elevator_id: String,
+        floor: usize,
+    },
+    /// Fire service Phase II: `person_id` operates the car from inside.
+    FirefighterControl {
+        elevator_id: String,
+        person_id: usize,
+    },
+    /// In Phase II only the firefighter's car calls, to floors the car
+    /// serves, are answered.
+    CarCallIgnored {
+        elevator_id: String,
+        person_id: usize,
+        floor: usize,
+    },
+    /// The car left Phase II and went back to the recall floor.
+    FirefighterReleased {
+        elevator_id: String,
+    },
+    /// Fire service is over; the cars take calls again.
+    FireServiceReset,
+    FaultInjected {
+        fault: Fault,
+    },
+    FaultCleared {
+        fault: Fault,
+    },
+    /// The doors would not close; the car stays at `floor` until they do.
+    DoorsJammed {
+        elevator_id: String,
+        floor: usize,
+    },
+    /// The floor sensor read `reported_floor` while the car was at `floor`.
+    SensorFault {
+        elevator_id: String,
+        reported_floor: usize,
+        floor: usize,
+    },
+    /// The car stopped dead at `position`, between floors if it is not whole.
+    CarStuck {
+        elevator_id: String,
+        position: f64,
+    },
+    /// The car was levelled at `floor` and the people inside got out.
+    CarRescued {
+        elevator_id: String,
+        floor: usize,
+        person_ids: Vec<usize>,
+    },
+    /// The car was taken out of service after its worker or lock failed, or
+    /// a fault played out in it.
+    CarIsolated {
+        elevator_id: String,
+        reason: String,
+    },
+    /// A message to the controller never arrived and will be sent again.
+    MessageLost {
+        message: String,
+    },
+    /// No more calls will be registered.
+    CallsComplete,
+    Error {
+        elevator_id: Option<String>,
+        message: String,
+    },
+}
+
+/// Receiver of `SimEvent`s, shared by every car and controller thread.
+pub trait EventSink: Send + Sync {
+    /// `at` is the time since the start of the run.
+    fn publish(&self, at: Duration, event: &SimEvent);
+}
+
+/// Prints events in the same format the simulations always used.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConsoleSink;
+
+impl EventSink for ConsoleSink {
+    fn publish(&self, _at: Duration, event: &SimEvent) {
+        match event {
+            SimEvent::CallRegistered { request } => println!(
+                "Person {} press lift button at floor {} to floor {} *****",
+                request.person_id, request.current_floor, request.target_floor
+            ),
+            SimEvent::CallRejected { request, reason } => println!(
+                "****ERROR: rejected request of person {}: {}",
+                request.person_id, reason
+            ),
+            SimEvent::CallCancelled { request } => println!(
+                "Person {} call from floor {} cancelled",
+                request.person_id, request.current_floor
+            ),
+            SimEvent::CarAssigned {
+                elevator_id,
+                person_id,
+            } => println!("Person {} assigned to elevator {}", person_id, elevator_id),
+            SimEvent::TransferPlanned { person_id, floor } => {
+                println!("Person {} transfers at floor {}", person_id, floor)
+            }
+            SimEvent::RequestsTaken {
+                elevator_id,
+                person_ids,
+            } => println!(
+                "\tElevator {} handle request of person {:?}",
+                elevator_id, person_ids
+            ),
+            SimEvent::CarArrived {
+                elevator_id,
+                floor,
+                direction,
+            } => match direction {
+                Some(Direction::Up) => println!(
+                    "\tElevator {} move up and stopped at floor {}",
+                    elevator_id, floor
+                ),
+                Some(Direction::Down) => println!(
+                    "\tElevator {} move down and stopped at floor {}",
+                    elevator_id, floor
+                ),
+                None => println!("\tElevator {} stopped at floor {}", elevator_id, floor),
+            },
+            SimEvent::PassengerBoarded {
+                elevator_id,
+                person_id,
+                floor,
+            } => println!(
+                "Person {} enters elevator {} at floor {}",
+                person_id, elevator_id, floor
+            ),
+            SimEvent::PassengerAlighted {
+                elevator_id,
+                person_id,
+                floor,
+            } => println!(
+                "Person {} exits elevator {} at floor {}",
+                person_id, elevator_id, floor
+            ),
+            SimEvent::CarFull {
+                elevator_id,
+                floor,
+                person_ids,
+            } => println!(
+                "\tElevator {} full at floor {}, skipped person {:?}",
+                elevator_id, floor, person_ids
+            ),
+            SimEvent::CarStopped { elevator_id } => println!("Elevator {elevator_id} stopped."),
+            SimEvent::MaintenanceStarted { elevator_id } => {
+                println!(
+                    "*** Elevator Controller: ELEVATOR {} BROKEN !!! ***",
+                    elevator_id
+                );
+                println!(
+                    "*** Elevator Controller: Elevator {} entering maintenance mode. ***",
+                    elevator_id
+                );
+                println!("*** ELEVATOR {} UNDER MAINTENANCE !!! ***: ", elevator_id);
+            }
+            SimEvent::CarEvacuated {
+                elevator_id,
+                floor,
+                person_ids,
+            } => println!(
+                "*** Elevator {} evacuated at floor {}, person {:?} got out ***",
+                elevator_id, floor, person_ids
+            ),
+            SimEvent::ReturnedToService { elevator_id } => println!(
+                "*** Elevator Controller: Elevator {} back in service. ***",
+                elevator_id
+            ),
+            SimEvent::PowerOutage => {
+                println!("!!!!!!!!!!!!!!! Alert: Power Outage Occur !!!!!!!!!!!!!!!!!");
+                println!("*** Elevator Controller: Cars switching to battery power. ***");
+            }
+            SimEvent::PowerRestored => {
+                println!("*** Elevator Controller: Power restored, back in service. ***")
+            }
+            SimEvent::FireRecall { floor } => {
+                println!("!!!!!!!!!!!!!!! Alert: Fire Recall to floor {floor} !!!!!!!!!!!!!!!!!")
+            }
+            SimEvent::CarRecalled { elevator_id, floor } => println!(
+                "*** Elevator {} parked at floor {} for fire service ***",
+                elevator_id, floor
+            ),
+            SimEvent::FirefighterControl {
+                elevator_id,
+                person_id,
+            } => println!(
+                "*** Elevator {} under firefighter control of person {} ***",
+                elevator_id, person_id
+            ),
+            SimEvent::CarCallIgnored {
+                elevator_id,
+                person_id,
+                floor,
+            } => println!(
+                "\tElevator {} ignored car call of person {} to floor {}",
+                elevator_id, person_id, floor
+            ),
+            SimEvent::FirefighterReleased { elevator_id } => {
+                println!("*** Elevator {} released by firefighter ***", elevator_id)
+            }
+            SimEvent::FireServiceReset => {
+                println!("*** Elevator Controller: Fire service reset, back in service. ***")
+            }
+            SimEvent::FaultInjected { fault } => println!("!!! Fault injected: {fault} !!!"),
+            SimEvent::FaultCleared { fault } => println!("!!! Fault cleared: {fault} !!!"),
+            SimEvent::DoorsJammed { elevator_id, floor } => {
+                println!("\tElevator {} doors jammed at floor {}", elevator_id, floor)
+            }
+            SimEvent::SensorFault {
+                elevator_id,
+                reported_floor,
+                floor,
+            } => println!(
+                "\tElevator {} floor sensor read {} at floor {}, running to the bottom to correct",
+                elevator_id, reported_floor, floor
+            ),
+            SimEvent::CarStuck {
+                elevator_id,
+                position,
+            } => println!(
+                "*** Elevator {} stuck at position {:.2} ***",
+                elevator_id, position
+            ),
+            SimEvent::CarRescued {
+                elevator_id,
+                floor,
+                person_ids,
+            } => println!(
+                "*** Elevator {} rescued at floor {}, person {:?} got out ***",
+                elevator_id, floor, person_ids
+            ),
+            SimEvent::CarIsolated {
+                elevator_id,
+                reason,
+            } => println!(
+                "*** Elevator Controller: Elevator {} isolated: {} ***",
+                elevator_id, reason
+            ),
+            SimEvent::MessageLost { message } => println!("\tMessage lost: {message}"),
+            SimEvent::CallsComplete => println!("Elevator Controller: No more people"),
+            SimEvent::Error { message, .. } => println!("****ERROR: {message}"),
+            SimEvent::HallCallLit { .. }
+            | SimEvent::HallCallCancelled { .. }
+            | SimEvent::CarCallLit { .. }
+            | SimEvent::CarCallCancelled { .. }
+            | SimEvent::DoorsOpened { .. }
+            | SimEvent::DoorsClosed { .. }
+            | SimEvent::CarIdle { .. } => {}
+        }
+    }
+}
+
+/// Keeps every event in memory, e.g. to check a run in a test.
+#[derive(Debug, Default)]
+pub struct EventRecorder {
+    events: Mutex<Vec<(Duration, SimEvent)>>,
+}
+
+impl EventRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything recorded so far, in publishing order.
+    pub fn events(&self) -> Result<Vec<(Duration, SimEvent)>, ElevatorError> {
+        Ok(lock(&self.events, "event recorder")?.clone())
+    }
+}
+
+impl EventSink for EventRecorder {
+    fn publish(&self, at: Duration, event: &SimEvent) {
+        match lock(&self.events, "event recorder") {
+            Ok(mut events) => events.push((at, event.clone())),
+            Err(e) => println!("****ERROR: cannot record event: {e}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    at: Duration,
+    #[serde(flatten)]
+    event: &'a SimEvent,
+}
+
+/// Writes one JSON object per event to a file, for other tools to read.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl JsonLinesSink {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(JsonLinesSink {
+            writer: Mutex::new(LineWriter::new(File::create(path)?)),
+        })
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn publish(&self, at: Duration, event: &SimEvent) {
+        let written = serde_json::to_string(&JsonLine { at, event })
+            .map_err(|e| e.to_string())
+            .and_then(|line| match lock(&self.writer, "event log") {
+                Ok(mut writer) => writeln!(writer, "{line}").map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            });
+        if let Err(e) = written {
+            println!("****ERROR: cannot write event: {e}");
+        }
+    }
+}
+
+/// Publishes every event to each of its sinks in turn.
+#[derive(Default, Clone)]
+pub struct Broadcast(pub Vec<Arc<dyn EventSink>>);
+
+impl EventSink for Broadcast {
+    fn publish(&self, at: Duration, event: &SimEvent) {
+        for sink in &self.0 {
+            sink.publish(at, event);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// A failure injected into a run, to check the controller copes with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    /// The doors jam the next time the car opens them at `floor`, holding
+    /// it there for `duration_ms`.
+    DoorJam {
+        elevator_id: String,
+        floor: usize,
+        duration_ms: u64,
+    },
+    /// The car stops dead between floors with its riders inside, until it
+    /// is rescued after `duration_ms`.
+    Stuck {
+        elevator_id: String,
+        duration_ms: u64,
+    },
+    /// The floor sensor reads `reported_floor` before the car's next trip.
+    WrongFloor {
+        elevator_id: String,
+        reported_floor: usize,
+    },
+    /// The next `count` calls sent to the controller are lost, and sent
+    /// again by whoever made them.
+    MessageLoss {
+        count: usize,
+    },
+    /// The car's worker thread panics when it next serves a batch.
+    WorkerPanic {
+        elevator_id: String,
+    },
+    /// Another thread panics while holding the car's lock.
+    PoisonLock {
+        elevator_id: String,
+    },
+    Maintenance {
+        elevator_id: String,
+        duration_ms: u64,
+    },
+    PowerOutage {
+        duration_ms: u64,
+    },
+}
+
+impl Fault {
+    /// Car the fault hits, if it hits a single one.
+    pub fn elevator_id(&self) -> Option<&str> {
+        match self {
+            Fault::DoorJam { elevator_id, .. }
+            | Fault::Stuck { elevator_id, .. }
+            | Fault::WrongFloor { elevator_id, .. }
+            | Fault::WorkerPanic { elevator_id }
+            | Fault::PoisonLock { elevator_id }
+            | Fault::Maintenance { elevator_id, .. } => Some(elevator_id),
+            Fault::MessageLoss { .. } | Fault::PowerOutage { .. } => None,
+        }
+    }
+
+    /// How long after it starts the fault is cleared, for faults that are
+    /// cleared at all.
+    pub fn clears_after(&self) -> Option<Duration> {
+        match self {
+            Fault::Stuck { duration_ms, .. }
+            | Fault::Maintenance { duration_ms, .. }
+            | Fault::PowerOutage { duration_ms } => Some(Duration::from_millis(*duration_ms)),
+            _ => None,
+        }
+    }
+
+    /// Parse the CSV fields after `fault,<at_ms>`, e.g. `door_jam,A,3,2000`.
+    pub fn from_csv(fields: &[&str]) -> Result<Self, String> {
+        fn parse<T: std::str::FromStr>(field: &str) -> Result<T, String> {
+            field
+                .parse()
+                .map_err(|_| format!("`{field}` is not a number"))
+        }
+
+        let fault = match fields {
+            ["door_jam", elevator_id, floor, duration_ms] => Fault::DoorJam {
+                elevator_id: elevator_id.to_string(),
+                floor: parse(floor)?,
+                duration_ms: parse(duration_ms)?,
+            },
+            ["stuck", elevator_id, duration_ms] => Fault::Stuck {
+                elevator_id: elevator_id.to_string(),
+                duration_ms: parse(duration_ms)?,
+            },
+            ["wrong_floor", elevator_id, reported_floor] => Fault::WrongFloor {
+                elevator_id: elevator_id.to_string(),
+                reported_floor: parse(reported_floor)?,
+            },
+            ["message_loss", count] => Fault::MessageLoss {
+                count: parse(count)?,
+            },
+            ["worker_panic", elevator_id] => Fault::WorkerPanic {
+                elevator_id: elevator_id.to_string(),
+            },
+            ["poison_lock", elevator_id] => Fault::PoisonLock {
+                elevator_id: elevator_id.to_string(),
+            },
+            ["maintenance", elevator_id, duration_ms] => Fault::Maintenance {
+                elevator_id: elevator_id.to_string(),
+                duration_ms: parse(duration_ms)?,
+            },
+            ["power_outage", duration_ms] => Fault::PowerOutage {
+                duration_ms: parse(duration_ms)?,
+            },
+            _ => return Err(format!("unrecognised fault `{}`", fields.join(","))),
+        };
+        Ok(fault)
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::DoorJam {
+                elevator_id,
+                floor,
+                duration_ms,
+            } => write!(
+                f,
+                "doors of elevator {elevator_id} jam at floor {floor} for {duration_ms} ms"
+            ),
+            Fault::Stuck {
+                elevator_id,
+                duration_ms,
+            } => write!(
+                f,
+                "elevator {elevator_id} stuck between floors for {duration_ms} ms"
+            ),
+            Fault::WrongFloor {
+                elevator_id,
+                reported_floor,
+            } => write!(
+                f,
+                "floor sensor of elevator {elevator_id} reads floor {reported_floor}"
+            ),
+            Fault::MessageLoss { count } => write!(f, "next {count} messages lost"),
+            Fault::WorkerPanic { elevator_id } => {
+                write!(f, "worker of elevator {elevator_id} panics")
+            }
+            Fault::PoisonLock { elevator_id } => {
+                write!(f, "lock of elevator {elevator_id} poisoned")
+            }
+            Fault::Maintenance {
+                elevator_id,
+                duration_ms,
+            } => write!(
+                f,
+                "elevator {elevator_id} under maintenance for {duration_ms} ms"
+            ),
+            Fault::PowerOutage { duration_ms } => write!(f, "power outage for {duration_ms} ms"),
+        }
+    }
+}
+
+/// A fault and when to inject it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledFault {
+    /// From the start of the run.
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+impl ScheduledFault {
+    pub fn at(&self) -> Duration {
+        Duration::from_millis(self.at_ms)
+    }
+}
+
+/// Jammed doors waiting to happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoorJam {
+    pub floor: usize,
+    pub duration: Duration,
+}
+
+/// Faults injected into one car that have not played out yet.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CarFaults {
+    pub door_jam: Option<DoorJam>,
+    /// Doors jammed open at the stop the car is making.
+    pub jammed: Option<DoorJam>,
+    /// What the floor sensor reads before the next trip.
+    pub misreported_floor: Option<usize>,
+    pub worker_panic: bool,
+    /// Stopped dead until rescued.
+    pub stuck: bool,
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Firefighters' emergency operation of the group.
+///
+/// Phase I recalls every car non-stop to the recall floor, cancelling all
+/// calls, and parks it there with its doors open. Phase II then hands single
+/// cars to a firefighter, whose car calls are the only ones the car answers.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FireService {
+    recall_floor: Option<usize>,
+    /// Cars in Phase II, with the person operating each.
+    operators: BTreeMap<String, usize>,
+}
+
+impl FireService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start Phase I. Returns false if the cars were already recalled.
+    pub fn recall(&mut self, floor: usize) -> bool {
+        if self.recall_floor.is_some() {
+            return false;
+        }
+        self.recall_floor = Some(floor);
+        true
+    }
+
+    /// End fire service, Phase II included. Returns false if it was not on.
+    pub fn reset(&mut self) -> bool {
+        self.operators.clear();
+        self.recall_floor.take().is_some()
+    }
+
+    /// Floor the cars were recalled to, while Phase I is on.
+    pub fn recall_floor(&self) -> Option<usize> {
+        self.recall_floor
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.recall_floor.is_some()
+    }
+
+    /// Put car `car` in Phase II, operated by `operator`. Returns false if the
+    /// cars are not recalled or someone already operates it.
+    pub fn take_control(&mut self, car: &str, operator: usize) -> bool {
+        if self.recall_floor.is_none() || self.operators.contains_key(car) {
+            return false;
+        }
+        self.operators.insert(car.to_string(), operator);
+        true
+    }
+
+    /// Take car `car` out of Phase II. Returns false if it was not in it.
+    pub fn release(&mut self, car: &str) -> bool {
+        self.operators.remove(car).is_some()
+    }
+
+    /// Person operating car `car` in Phase II.
+    pub fn operator(&self, car: &str) -> Option<usize> {
+        self.operators.get(car).copied()
+    }
+}
+
+/// A command from the fire panel in the lobby or a firefighter's key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum FireCommand {
+    /// Phase I: recall every car to `floor`.
+    Recall { floor: usize },
+    /// Phase II: firefighter `person_id` takes car `elevator_id` with the key.
+    TakeControl {
+        elevator_id: String,
+        person_id: usize,
+    },
+    /// Car call pressed by `person_id` inside a car in Phase II.
+    CarCall {
+        elevator_id: String,
+        person_id: usize,
+        floor: usize,
+    },
+    /// The key is turned off; the car goes back to the recall floor.
+    Release { elevator_id: String },
+    /// Fire service is over.
+    Reset,
+}
+
+impl FireCommand {
+    /// Car the command is for, if it is for a single one.
+    pub fn elevator_id(&self) -> Option<&str> {
+        match self {
+            FireCommand::TakeControl { elevator_id, .. }
+            | FireCommand::CarCall { elevator_id, .. }
+            | FireCommand::Release { elevator_id } => Some(elevator_id),
+            FireCommand::Recall { .. } | FireCommand::Reset => None,
+        }
+    }
+
+    /// Floor the command sends cars to, if any.
+    pub fn floor(&self) -> Option<usize> {
+        match self {
+            FireCommand::Recall { floor } | FireCommand::CarCall { floor, .. } => Some(*floor),
+            _ => None,
+        }
+    }
+
+    /// Parse the CSV fields after `fire,<at_ms>`, e.g. `take_control,A,9`.
+    pub fn from_csv(fields: &[&str]) -> Result<Self, String> {
+        fn parse(field: &str) -> Result<usize, String> {
+            field
+                .parse()
+                .map_err(|_| format!("`{field}` is not a number"))
+        }
+
+        let command = match fields {
+            ["recall", floor] => FireCommand::Recall {
+                floor: parse(floor)?,
+            },
+            ["take_control", elevator_id, person_id] => FireCommand::TakeControl {
+                elevator_id: elevator_id.to_string(),
+                person_id: parse(person_id)?,
+            },
+            ["car_call", elevator_id, person_id, floor] => FireCommand::CarCall {
+                elevator_id: elevator_id.to_string(),
+                person_id: parse(person_id)?,
+                floor: parse(floor)?,
+            },
+            ["release", elevator_id] => FireCommand::Release {
+                elevator_id: elevator_id.to_string(),
+            },
+            ["reset"] => FireCommand::Reset,
+            _ => return Err(format!("unrecognised fire command `{}`", fields.join(","))),
+        };
+        Ok(command)
+    }
+}
+
+impl fmt::Display for FireCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FireCommand::Recall { floor } => write!(f, "fire recall to floor {floor}"),
+            FireCommand::TakeControl {
+                elevator_id,
+                person_id,
+            } => write!(
+                f,
+                "firefighter {person_id} takes control of elevator {elevator_id}"
+            ),
+            FireCommand::CarCall {
+                elevator_id,
+                person_id,
+                floor,
+            } => write!(
+                f,
+                "person {person_id} in elevator {elevator_id} calls floor {floor}"
+            ),
+            FireCommand::Release { elevator_id } => {
+                write!(f, "firefighter releases elevator {elevator_id}")
+            }
+            FireCommand::Reset => write!(f, "fire service reset"),
+        }
+    }
+}
+
+/// A fire command and when to give it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledFireCommand {
+    /// From the start of the run.
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub command: FireCommand,
+}
+
+impl ScheduledFireCommand {
+    pub fn at(&self) -> Duration {
+        Duration::from_millis(self.at_ms)
+    }
+}
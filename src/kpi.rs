@@ -0,0 +1,253 @@
+use crate::{lock, ButtonPressed, Elevator, ElevatorError};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Average, percentiles and maximum of one passenger time.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    pub fn new(mut times: Vec<Duration>) -> Self {
+        if times.is_empty() {
+            return Summary::default();
+        }
+        times.sort();
+
+        // Nearest-rank percentile
+        let percentile = |p: usize| times[(p * times.len()).div_ceil(100).max(1) - 1];
+
+        Summary {
+            count: times.len(),
+            average: times.iter().sum::<Duration>() / times.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            max: times[times.len() - 1],
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "avg {:.1?}, p50 {:.1?}, p95 {:.1?}, max {:.1?}",
+            self.average, self.p50, self.p95, self.max
+        )
+    }
+}
+
+/// Waiting, ride and journey times of the people behind a set of finished
+/// legs.
+///
+/// Legs of the same person, such as either side of a sky lobby, count as one
+/// journey: its waiting and ride times add up over the legs, and its journey
+/// time runs from the first call to the last time the person got off.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JourneyStats {
+    pub passengers: usize,
+    pub waiting_time: Summary,
+    pub ride_time: Summary,
+    pub journey_time: Summary,
+}
+
+impl JourneyStats {
+    pub fn new<'a>(legs: impl IntoIterator<Item = &'a ButtonPressed>) -> Self {
+        let mut journeys = BTreeMap::<usize, Vec<&ButtonPressed>>::new();
+        for leg in legs {
+            journeys.entry(leg.person_id).or_default().push(leg);
+        }
+        let total = |time: fn(&ButtonPressed) -> Option<Duration>| {
+            Summary::new(
+                journeys
+                    .values()
+                    .filter_map(|legs| legs.iter().map(|leg| time(leg)).sum())
+                    .collect(),
+            )
+        };
+
+        JourneyStats {
+            passengers: journeys.len(),
+            waiting_time: total(ButtonPressed::waiting_time),
+            ride_time: total(ButtonPressed::ride_time),
+            // Every leg keeps the time of the first call, so the last one
+            // to finish spans the whole journey
+            journey_time: Summary::new(
+                journeys
+                    .values()
+                    .filter_map(|legs| legs.iter().filter_map(|leg| leg.journey_time()).max())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// The passenger who waited longest for a car, over all legs of the journey.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongestWait {
+    pub person_id: usize,
+    /// Cars the person rode, in boarding order.
+    pub elevator_ids: Vec<String>,
+    pub waiting_time: Duration,
+}
+
+/// KPIs of one car.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarReport {
+    pub elevator_id: String,
+    pub journeys: JourneyStats,
+    pub capacity: usize,
+    pub peak_load: usize,
+    /// Stops where someone was left behind because the car was full.
+    pub times_full: usize,
+}
+
+/// End-of-run passenger KPIs, per car and for the whole fleet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KpiReport {
+    pub cars: Vec<CarReport>,
+    pub fleet: JourneyStats,
+    pub longest_wait: Option<LongestWait>,
+}
+
+impl KpiReport {
+    pub fn new(elevators: &[Arc<Mutex<Elevator>>]) -> Result<Self, ElevatorError> {
+        let mut cars = Vec::new();
+        let mut journeys = Vec::new();
+        for elevator in elevators {
+            let elevator = lock(elevator, "elevator")?;
+            cars.push(CarReport {
+                elevator_id: elevator.id.clone(),
+                journeys: JourneyStats::new(&elevator.completed),
+                capacity: elevator.capacity,
+                peak_load: elevator.peak_load,
+                times_full: elevator.times_full,
+            });
+            journeys.push((elevator.id.clone(), elevator.completed.clone()));
+        }
+
+        let fleet = JourneyStats::new(journeys.iter().flat_map(|(_, completed)| completed));
+        // Added up per person, like the fleet's waiting time
+        let mut people = BTreeMap::<usize, Vec<(&String, &ButtonPressed)>>::new();
+        for (id, completed) in &journeys {
+            for leg in completed {
+                people.entry(leg.person_id).or_default().push((id, leg));
+            }
+        }
+        let longest_wait = people
+            .into_iter()
+            .filter_map(|(person_id, mut legs)| {
+                let waiting_time = legs
+                    .iter()
+                    .map(|(_, leg)| leg.waiting_time())
+                    .sum::<Option<_>>()?;
+                legs.sort_by_key(|(_, leg)| leg.boarded_at);
+                let elevator_ids = legs.into_iter().map(|(id, _)| id.clone()).collect();
+                Some(LongestWait {
+                    person_id,
+                    elevator_ids,
+                    waiting_time,
+                })
+            })
+            .max_by_key(|longest| longest.waiting_time);
+
+        Ok(KpiReport {
+            cars,
+            fleet,
+            longest_wait,
+        })
+    }
+}
+
+impl fmt::Display for KpiReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = |f: &mut fmt::Formatter<'_>, name: &str, stats: &JourneyStats| {
+            writeln!(f, "{name}: {} passengers", stats.passengers)?;
+            writeln!(f, "\twaiting time: {}", stats.waiting_time)?;
+            writeln!(f, "\tride time:    {}", stats.ride_time)?;
+            writeln!(f, "\tjourney time: {}", stats.journey_time)
+        };
+
+        writeln!(f, "===== Passenger KPIs =====")?;
+        for car in &self.cars {
+            section(f, &format!("Elevator {}", car.elevator_id), &car.journeys)?;
+            writeln!(
+                f,
+                "\tpeak load:    {}/{}, full {} times",
+                car.peak_load, car.capacity, car.times_full
+            )?;
+        }
+        section(f, "Fleet", &self.fleet)?;
+        if let Some(longest) = &self.longest_wait {
+            writeln!(
+                f,
+                "Longest wait: person {} for {:.1?} (elevator {})",
+                longest.person_id,
+                longest.waiting_time,
+                longest.elevator_ids.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(person_id: usize, times: [u64; 3], transferred_at: Option<u64>) -> ButtonPressed {
+        let [registered_at, boarded_at, alighted_at] = times.map(Duration::from_secs);
+        ButtonPressed {
+            registered_at: Some(registered_at),
+            transferred_at: transferred_at.map(Duration::from_secs),
+            boarded_at: Some(boarded_at),
+            alighted_at: Some(alighted_at),
+            ..ButtonPressed::new_request(person_id, 0, 1)
+        }
+    }
+
+    #[test]
+    fn legs_of_one_person_make_one_journey() {
+        // Person 1 changes cars at a sky lobby at 30s, person 2 rides once
+        // and has the longest single wait
+        let legs = [
+            leg(1, [0, 10, 30], None),
+            leg(1, [0, 40, 60], Some(30)),
+            leg(2, [5, 20, 25], None),
+        ];
+
+        let stats = JourneyStats::new(&legs);
+        assert_eq!(stats.passengers, 2);
+        assert_eq!(stats.waiting_time.max, Duration::from_secs(20));
+        assert_eq!(stats.ride_time.max, Duration::from_secs(40));
+        assert_eq!(stats.journey_time.max, Duration::from_secs(60));
+        assert_eq!(stats.journey_time.p50, Duration::from_secs(20));
+
+        let low = Elevator::new_elevator("L".to_string(), 0);
+        let high = Elevator::new_elevator("H".to_string(), 10);
+        let cars = [(low, [legs[0], legs[2]].to_vec()), (high, vec![legs[1]])].map(
+            |(mut car, completed)| {
+                car.completed = completed;
+                Arc::new(Mutex::new(car))
+            },
+        );
+        let report = KpiReport::new(&cars).unwrap();
+        assert_eq!(report.fleet, stats);
+        assert_eq!(
+            report.longest_wait,
+            Some(LongestWait {
+                person_id: 1,
+                elevator_ids: vec!["L".to_string(), "H".to_string()],
+                waiting_time: Duration::from_secs(20),
+            })
+        );
+    }
+}
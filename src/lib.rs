@@ -1,15 +1,40 @@
-use scheduled_thread_pool::JobHandle;
+use calls::{CallLamps, CarCall, HallCall};
+use events::{Broadcast, ConsoleSink, EventSink, JsonLinesSink, SimEvent};
+use faults::{CarFaults, DoorJam, Fault};
+use fire::{FireCommand, FireService};
+use kpi::KpiReport;
+use maintenance::{MaintenanceRegistry, MaintenanceWindow};
+use motion::MotionProfile;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use scenario::Scenario;
+use scheduled_thread_pool::{JobHandle, ScheduledThreadPool};
 use serde::{Deserialize, Serialize};
 use std::{
+    any::Any,
     cmp::Ordering,
     collections::VecDeque,
+    fmt,
+    panic::{self, AssertUnwindSafe},
     sync::{
+        atomic::{self, AtomicU64},
         mpsc::{self, Receiver},
-        Arc, Mutex, MutexGuard,
+        Arc, Mutex, MutexGuard, PoisonError, TryLockError,
     },
+    thread,
+    time::{Duration, Instant},
 };
+use threadpool::ThreadPool;
 
+pub mod calls;
+pub mod events;
+pub mod faults;
+pub mod fire;
+pub mod kpi;
+pub mod maintenance;
+pub mod motion;
+pub mod scenario;
 pub mod simulations;
+pub mod traffic;
 // use simulations::concurrency_elevator_system::concurrency_elevator_system;
 // use simulations::scheduling_elevator_system::scheduling_elevator_system;
 
@@ -17,13 +42,27 @@ pub mod simulations;
 pub enum Message {
     ButtonPressed(ButtonPressed),
     ElevatorUnderMaintenance(String),
+    ElevatorReturnedToService(String),
+    Fire(FireCommand),
     Complete(bool),
 }
 
+impl Message {
+    pub fn encode(&self) -> Result<String, ElevatorError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn decode(body: &[u8]) -> Result<Self, ElevatorError> {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
 #[derive(Debug)]
 pub enum ElevatorEvent {
     Maintenance(String),
+    ReturnedToService(String),
     ButtonPress(ButtonPressed),
+    Fire(FireCommand),
     Complete,
 }
 
@@ -34,6 +73,212 @@ pub enum QueueStatus {
     Done,
 }
 
+/// A button press that happens at a given point in time from the start of a run.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TimedRequest {
+    pub at: Duration,
+    pub request: ButtonPressed,
+}
+
+/// Settings shared by every simulation entry point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Seed of the one RNG every random draw in a run comes from.
+    pub seed: u64,
+    /// JSON-lines file to write every event of the run to.
+    pub events_path: Option<String>,
+    /// Name of the dispatch strategy every car uses, one of
+    /// `DISPATCH_STRATEGIES`; each car keeps its default without one.
+    pub strategy: Option<String>,
+    /// Name of the way hall calls reach the cars, one of `DISPATCH_MODES`;
+    /// collective control without one.
+    pub dispatch: Option<String>,
+}
+
+impl SimulationConfig {
+    /// Environment variable holding the seed of a run to replay.
+    pub const SEED_VAR: &'static str = "ELEVATOR_SEED";
+
+    /// Environment variable naming a file to record the run's events in.
+    pub const EVENTS_VAR: &'static str = "ELEVATOR_EVENTS";
+
+    /// Environment variable naming the dispatch strategy of every car.
+    pub const STRATEGY_VAR: &'static str = "ELEVATOR_STRATEGY";
+
+    /// Environment variable naming the dispatch mode of the fleet.
+    pub const DISPATCH_VAR: &'static str = "ELEVATOR_DISPATCH";
+
+    /// Use the seed from `ELEVATOR_SEED` if it is set, a fresh one otherwise.
+    pub fn from_env() -> Self {
+        let seed = std::env::var(Self::SEED_VAR)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| rand::thread_rng().gen());
+
+        SimulationConfig {
+            seed,
+            events_path: std::env::var(Self::EVENTS_VAR).ok(),
+            strategy: std::env::var(Self::STRATEGY_VAR).ok(),
+            dispatch: std::env::var(Self::DISPATCH_VAR).ok(),
+        }
+    }
+
+    /// Console output, plus the events file when one was asked for.
+    pub fn event_sink(&self) -> Arc<dyn EventSink> {
+        let mut sinks: Vec<Arc<dyn EventSink>> = vec![Arc::new(ConsoleSink)];
+        if let Some(path) = &self.events_path {
+            match JsonLinesSink::create(path) {
+                Ok(sink) => sinks.push(Arc::new(sink)),
+                Err(e) => println!("****ERROR: cannot create {path}: {e}"),
+            }
+        }
+
+        Arc::new(Broadcast(sinks))
+    }
+
+    /// The strategy asked for, in a building whose highest floor is
+    /// `top_floor`.
+    pub fn dispatch_strategy(&self, top_floor: usize) -> Option<Arc<dyn DispatchStrategy>> {
+        let name = self.strategy.as_deref()?;
+        let strategy = dispatch_strategy(name, top_floor);
+        if strategy.is_none() {
+            println!(
+                "****ERROR: unknown dispatch strategy {name}, expected one of {}",
+                DISPATCH_STRATEGIES.join(", ")
+            );
+        }
+        strategy
+    }
+
+    /// The dispatch mode asked for, collective control if none or an unknown
+    /// one was.
+    pub fn dispatch_mode(&self) -> DispatchMode {
+        let Some(name) = self.dispatch.as_deref() else {
+            return DispatchMode::default();
+        };
+        dispatch_mode(name).unwrap_or_else(|| {
+            println!(
+                "****ERROR: unknown dispatch mode {name}, expected one of {}",
+                DISPATCH_MODES.join(", ")
+            );
+            DispatchMode::default()
+        })
+    }
+
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Set up `cars` for `scenario` the same way in every simulation: its
+    /// floors, the event sink, the dispatch strategy and mode asked for, and
+    /// the scenario's maintenance windows.
+    pub fn configure(
+        &self,
+        cars: &mut impl Configurable,
+        scenario: &Scenario,
+    ) -> Result<(), ElevatorError> {
+        cars.set_floors(scenario.floors);
+        cars.set_mode(self.dispatch_mode());
+        cars.set_event_sink(self.event_sink())?;
+        if let Some(strategy) = self.dispatch_strategy(scenario.top_floor()) {
+            cars.set_strategy(strategy)?;
+        }
+        for window in &scenario.maintenance {
+            cars.schedule_maintenance(window.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/// Cars `SimulationConfig::configure` can set up: a threaded `Fleet`, or the
+/// `GroupController` the discrete-event simulation drives.
+pub trait Configurable {
+    fn set_floors(&mut self, floors: usize);
+    fn set_mode(&mut self, mode: DispatchMode);
+    fn set_event_sink(&mut self, events: Arc<dyn EventSink>) -> Result<(), ElevatorError>;
+    fn set_strategy(&self, strategy: Arc<dyn DispatchStrategy>) -> Result<(), ElevatorError>;
+    fn schedule_maintenance(&self, window: MaintenanceWindow) -> Result<(), ElevatorError>;
+}
+
+impl Configurable for GroupController {
+    fn set_floors(&mut self, floors: usize) {
+        self.floors = Some(floors);
+    }
+
+    fn set_mode(&mut self, mode: DispatchMode) {
+        self.mode = mode;
+    }
+
+    fn set_event_sink(&mut self, events: Arc<dyn EventSink>) -> Result<(), ElevatorError> {
+        GroupController::set_event_sink(self, events)
+    }
+
+    fn set_strategy(&self, strategy: Arc<dyn DispatchStrategy>) -> Result<(), ElevatorError> {
+        GroupController::set_strategy(self, strategy)
+    }
+
+    fn schedule_maintenance(&self, window: MaintenanceWindow) -> Result<(), ElevatorError> {
+        GroupController::schedule_maintenance(self, window)
+    }
+}
+
+impl Configurable for Fleet {
+    fn set_floors(&mut self, floors: usize) {
+        self.floors = Some(floors);
+    }
+
+    fn set_mode(&mut self, mode: DispatchMode) {
+        self.mode = mode;
+    }
+
+    fn set_event_sink(&mut self, events: Arc<dyn EventSink>) -> Result<(), ElevatorError> {
+        Fleet::set_event_sink(self, events)
+    }
+
+    fn set_strategy(&self, strategy: Arc<dyn DispatchStrategy>) -> Result<(), ElevatorError> {
+        Fleet::set_strategy(self, strategy)
+    }
+
+    fn schedule_maintenance(&self, window: MaintenanceWindow) -> Result<(), ElevatorError> {
+        self.controller().schedule_maintenance(window)
+    }
+}
+
+/// Source of timestamps, as time since the start of a run.
+#[derive(Debug, Clone)]
+pub enum Clock {
+    /// Real time, for the threaded simulations.
+    Wall(Instant),
+    /// Building time in nanoseconds, moved forward by a discrete-event
+    /// simulator.
+    Virtual(Arc<AtomicU64>),
+}
+
+impl Clock {
+    pub fn wall() -> Self {
+        Clock::Wall(Instant::now())
+    }
+
+    pub fn virtual_time() -> Self {
+        Clock::Virtual(Arc::new(AtomicU64::new(0)))
+    }
+
+    pub fn now(&self) -> Duration {
+        match self {
+            Clock::Wall(start) => start.elapsed(),
+            Clock::Virtual(now) => Duration::from_nanos(now.load(atomic::Ordering::SeqCst)),
+        }
+    }
+
+    /// Move a virtual clock to `now`. A wall clock ignores this.
+    pub fn set(&self, now: Duration) {
+        if let Clock::Virtual(time) = self {
+            let nanos = u64::try_from(now.as_nanos()).unwrap_or(u64::MAX);
+            time.store(nanos, atomic::Ordering::SeqCst);
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Data {
     pub queue_status: QueueStatus,
@@ -44,7 +289,240 @@ pub struct Elevator {
     pub id: String,
     pub elevator_current_floor: usize,
     pub capacity: usize,
-    pub status: String,
+    pub status: ElevatorState,
+    pub direction: Option<Direction>,
+    pub passengers: Vec<ButtonPressed>,
+    pub requests: Vec<ButtonPressed>,
+    pub strategy: Arc<dyn DispatchStrategy>,
+    pub config: ElevatorConfig,
+    pub cost_model: CostModel,
+    pub clock: Clock,
+    /// Building time at which the car finishes its last stop. The car runs
+    /// ahead of the wall clock in threaded runs, so stops are timed from here.
+    pub busy_until: Duration,
+    /// The car's current or most recent trip, to tell where it is on the way.
+    pub trip: Option<Trip>,
+    /// Everyone delivered by this car, with their lifecycle timestamps.
+    /// A journey with a transfer counts once per leg.
+    pub completed: Vec<ButtonPressed>,
+    /// Next legs of people who got off at a transfer floor, waiting to be
+    /// registered with the group controller.
+    pub transfers: Vec<ButtonPressed>,
+    /// Most passengers inside the car at once.
+    pub peak_load: usize,
+    /// Stops where someone was left behind because the car was full.
+    pub times_full: usize,
+    /// Button lamps, shared with the group controller and the other cars.
+    pub lamps: Arc<Mutex<CallLamps>>,
+    /// Cars out of service, shared with the group controller.
+    pub maintenance: Arc<Mutex<MaintenanceRegistry>>,
+    /// Whether the building has lost mains power, shared with the group
+    /// controller.
+    pub power_outage: Arc<Mutex<bool>>,
+    /// Firefighters' service, shared with the group controller.
+    pub fire_service: Arc<Mutex<FireService>>,
+    /// Injected faults still to play out.
+    pub faults: CarFaults,
+    pub events: Arc<dyn EventSink>,
+}
+
+/// Mass assumed per passenger when checking a car's rated load.
+pub const AVERAGE_PASSENGER_KG: u32 = 75;
+
+/// Physical make-up of one car.
+///
+/// Every field has a default, so a scenario only needs to list what differs,
+/// e.g. a freight car with a large rated load but a low speed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElevatorConfig {
+    /// Most people allowed in the car.
+    pub rated_persons: usize,
+    pub rated_load_kg: u32,
+    /// Cruising speed, in metres per second.
+    pub max_speed: f64,
+    /// In metres per second squared, used for speeding up and slowing down alike.
+    pub acceleration: f64,
+    /// How quickly acceleration builds up, in metres per second cubed.
+    pub jerk: f64,
+    /// In metres.
+    pub floor_height: f64,
+    pub door_open_ms: u64,
+    /// Shortest time the doors stay fully open at a stop.
+    pub door_dwell_ms: u64,
+    pub door_close_ms: u64,
+    /// Time for one person to get in or out.
+    pub passenger_transfer_ms: u64,
+    /// Cruising speed on battery power during a power failure, in metres
+    /// per second.
+    pub battery_speed: f64,
+    /// Floor the car takes everyone to when the power fails. Without one the
+    /// car stops at the nearest floor.
+    pub evacuation_floor: Option<usize>,
+    /// Floors the car stops at. Empty means every floor.
+    pub served_floors: Vec<usize>,
+    /// Floor the car starts at and returns to when it has nothing to do.
+    /// Without one the car starts at the ground floor and stays wherever its
+    /// last trip ended.
+    pub home_floor: Option<usize>,
+}
+
+impl Default for ElevatorConfig {
+    fn default() -> Self {
+        ElevatorConfig {
+            rated_persons: 5,
+            rated_load_kg: 400,
+            max_speed: 2.0,
+            acceleration: 1.0,
+            jerk: 1.0,
+            floor_height: 3.0,
+            door_open_ms: 1000,
+            door_dwell_ms: 2000,
+            door_close_ms: 1000,
+            passenger_transfer_ms: 1000,
+            battery_speed: 0.5,
+            evacuation_floor: None,
+            served_floors: Vec::new(),
+            home_floor: None,
+        }
+    }
+}
+
+impl ElevatorConfig {
+    /// Passengers the car takes, limited by both head count and rated load.
+    pub fn capacity(&self) -> usize {
+        self.rated_persons
+            .min((self.rated_load_kg / AVERAGE_PASSENGER_KG) as usize)
+    }
+
+    pub fn serves(&self, floor: usize) -> bool {
+        self.served_floors.is_empty() || self.served_floors.contains(&floor)
+    }
+
+    /// `floor` if the car serves it, or else the served floor closest to it.
+    pub fn closest_served(&self, floor: usize) -> usize {
+        if self.serves(floor) {
+            return floor;
+        }
+        self.served_floors
+            .iter()
+            .copied()
+            .min_by_key(|served| served.abs_diff(floor))
+            .unwrap_or(floor)
+    }
+
+    /// Whether the car can take `request` from its floor to its destination.
+    pub fn serves_trip(&self, request: &ButtonPressed) -> bool {
+        self.serves(request.current_floor) && self.serves(request.target_floor)
+    }
+
+    pub fn cost_model(&self) -> CostModel {
+        CostModel {
+            motion: MotionProfile {
+                max_speed: self.max_speed,
+                acceleration: self.acceleration,
+                jerk: self.jerk,
+            },
+            floor_height: self.floor_height,
+            door_open_time: Duration::from_millis(self.door_open_ms),
+            door_dwell_time: Duration::from_millis(self.door_dwell_ms),
+            door_close_time: Duration::from_millis(self.door_close_ms),
+            passenger_transfer_time: Duration::from_millis(self.passenger_transfer_ms),
+            battery_speed: self.battery_speed,
+        }
+    }
+}
+
+/// Timings used to turn stops and floors travelled into time.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct CostModel {
+    pub motion: MotionProfile,
+    /// In metres.
+    pub floor_height: f64,
+    pub door_open_time: Duration,
+    pub door_dwell_time: Duration,
+    pub door_close_time: Duration,
+    pub passenger_transfer_time: Duration,
+    /// Cruising speed on battery power, in metres per second.
+    pub battery_speed: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        ElevatorConfig::default().cost_model()
+    }
+}
+
+impl CostModel {
+    /// Time to travel `floors` floors without stopping in between.
+    pub fn travel_time(&self, floors: usize) -> Duration {
+        self.motion.travel_time(floors as f64 * self.floor_height)
+    }
+
+    /// Time to cover `floors` floors, from a standstill, on battery power.
+    pub fn battery_travel_time(&self, floors: f64) -> Duration {
+        MotionProfile {
+            max_speed: self.battery_speed,
+            ..self.motion
+        }
+        .travel_time(floors * self.floor_height)
+    }
+
+    /// Floors covered `elapsed` into a trip of `floors` floors.
+    pub fn floors_at(&self, floors: usize, elapsed: Duration) -> f64 {
+        self.motion
+            .distance_at(floors as f64 * self.floor_height, elapsed)
+            / self.floor_height
+    }
+
+    /// Time from the doors starting to open until they are closed again,
+    /// when `transfers` people get in or out. The doors stay open for the
+    /// dwell time, or longer if people are still moving through them.
+    pub fn stop_time(&self, transfers: usize) -> Duration {
+        self.door_open_time
+            + self
+                .door_dwell_time
+                .max(self.passenger_transfer_time * transfers as u32)
+            + self.door_close_time
+    }
+}
+
+/// The trip a car is on, or last made.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Trip {
+    pub from_floor: usize,
+    pub to_floor: usize,
+    /// Building time the car set off.
+    pub departed_at: Duration,
+    pub travel_time: Duration,
+}
+
+/// Estimated time for a car to serve one request.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Eta {
+    /// Until the car arrives at the person's floor.
+    pub pick_up: Duration,
+    /// Until the car arrives at the person's destination.
+    pub destination: Duration,
+    /// Stops the car makes up to and including the destination.
+    pub stops: usize,
+}
+
+/// Point-in-time copy of an elevator's state, safe to hand to other threads.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElevatorSnapshot {
+    pub id: String,
+    pub elevator_current_floor: usize,
+    pub capacity: usize,
+    pub status: ElevatorState,
+    pub direction: Option<Direction>,
+    pub door_open: bool,
+    /// Where the car is, in floors; fractional while it is between floors.
+    pub position: f64,
+    pub load: usize,
+    pub passengers: Vec<usize>,
+    /// Floors whose button is lit inside the car.
+    pub car_calls: Vec<usize>,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
@@ -53,6 +531,25 @@ pub struct ButtonPressed {
     pub current_floor: usize,
     pub target_floor: usize,
     pub entered: bool,
+    /// Where the person is finally headed when `target_floor` is only a
+    /// transfer floor, such as a sky lobby, on the way there.
+    #[serde(default)]
+    pub destination: Option<usize>,
+    /// When the call reached the group controller. A later leg of the same
+    /// journey keeps the time of the first call.
+    #[serde(default)]
+    pub registered_at: Option<Duration>,
+    /// When the person got off one car to call another, at a transfer floor
+    /// or after being let out of a car taken out of service.
+    #[serde(default)]
+    pub transferred_at: Option<Duration>,
+    /// When a car took the call on.
+    #[serde(default)]
+    pub assigned_at: Option<Duration>,
+    #[serde(default)]
+    pub boarded_at: Option<Duration>,
+    #[serde(default)]
+    pub alighted_at: Option<Duration>,
 }
 
 impl ButtonPressed {
@@ -62,264 +559,3285 @@ impl ButtonPressed {
             current_floor: c_floor,
             target_floor: t_floor,
             entered: false,
+            destination: None,
+            registered_at: None,
+            transferred_at: None,
+            assigned_at: None,
+            boarded_at: None,
+            alighted_at: None,
         }
     }
-}
 
-#[derive(PartialEq)]
-pub enum Direction {
-    Up,
-    Down,
-}
+    /// A request checked against a building with `floors` floors.
+    pub fn try_new(
+        person_id: usize,
+        current_floor: usize,
+        target_floor: usize,
+        floors: usize,
+    ) -> Result<Self, RequestError> {
+        let request = Self::new_request(person_id, current_floor, target_floor);
+        request.validate(Some(floors))?;
+        Ok(request)
+    }
 
-impl Elevator {
-    pub fn new_elevator(elevator_id: String, current_floor: usize) -> Self {
-        Elevator {
-            id: elevator_id,
-            elevator_current_floor: current_floor,
-            capacity: 5,
-            status: "Idle".to_string(),
+    /// Reject zero-length trips, and floors outside the building when its
+    /// number of `floors` is known.
+    pub fn validate(&self, floors: Option<usize>) -> Result<(), RequestError> {
+        if let Some(floors) = floors {
+            for floor in [self.current_floor, self.target_floor] {
+                if floor >= floors {
+                    return Err(RequestError::FloorOutOfRange { floor, floors });
+                }
+            }
         }
+        if self.current_floor == self.target_floor {
+            return Err(RequestError::SameFloor {
+                person_id: self.person_id,
+                floor: self.current_floor,
+            });
+        }
+        Ok(())
     }
 
-    /// Find all users to fetch.
-    pub fn process_requests(
-        &self,
-        mut queue: MutexGuard<VecDeque<ButtonPressed>>,
-    ) -> Option<VecDeque<ButtonPressed>> {
-        let mut request_queue = VecDeque::new();
-
-        // Pop the first request from the queue
-        if let Some(request) = queue.pop_front() {
-            request_queue.push_back(request);
+    /// From registering the call until boarding a car.
+    pub fn waiting_time(&self) -> Option<Duration> {
+        let called_at = self.transferred_at.or(self.registered_at)?;
+        Some(self.boarded_at?.saturating_sub(called_at))
+    }
 
-            let initial_direction = if request.target_floor < request.current_floor {
-                Direction::Down
-            } else {
-                Direction::Up
-            };
+    /// From boarding until getting off at the destination.
+    pub fn ride_time(&self) -> Option<Duration> {
+        Some(self.alighted_at?.saturating_sub(self.boarded_at?))
+    }
 
-            // Check if there are other requests heading in the same direction
-            let mut i = 0;
-            while i < queue.len() {
-                if let Some(next_request) = queue.get(i) {
-                    let next_request_direction =
-                        if next_request.target_floor < next_request.current_floor {
-                            Direction::Down
-                        } else {
-                            Direction::Up
-                        };
+    /// From registering the call until getting off at the destination.
+    pub fn journey_time(&self) -> Option<Duration> {
+        Some(self.alighted_at?.saturating_sub(self.registered_at?))
+    }
 
-                    // Check if the next request is in the same direction
-                    let is_same_direction = initial_direction == next_request_direction;
+    /// Direction the person wants to travel in.
+    pub fn direction(&self) -> Direction {
+        if self.target_floor < self.current_floor {
+            Direction::Down
+        } else {
+            Direction::Up
+        }
+    }
 
-                    let is_in_direction = match initial_direction {
-                        Direction::Up => next_request.current_floor >= request.current_floor,
-                        Direction::Down => next_request.current_floor <= request.current_floor,
-                    };
+    /// The landing button this person pressed.
+    pub fn hall_call(&self) -> HallCall {
+        HallCall {
+            floor: self.current_floor,
+            direction: self.direction(),
+        }
+    }
 
-                    if is_same_direction && is_in_direction {
-                        if request_queue.len() == self.capacity {
-                            break;
-                        } else {
-                            let new_request = queue.remove(i).unwrap();
-                            request_queue.push_back(new_request);
-                            continue;
-                        }
-                    }
-                }
-                i += 1;
-            }
+    /// The button this person presses once inside car `car`.
+    pub fn car_call(&self, car: &str) -> CarCall {
+        CarCall {
+            car: car.to_string(),
+            floor: self.target_floor,
+        }
+    }
 
-            Some(request_queue)
+    /// Floor the car has to stop at next for this person.
+    pub fn stop_floor(&self) -> usize {
+        if self.entered {
+            self.target_floor
         } else {
-            // If no request is found, return None
-            None
+            self.current_floor
         }
     }
+}
 
-    pub fn move_elevator(&mut self, mut request_queue: Vec<ButtonPressed>, direction: Direction) {
-        loop {
-            let floor = match direction {
-                Direction::Up => request_queue.iter().min_by_key(|r| {
-                    if r.entered {
-                        r.target_floor
-                    } else {
-                        r.current_floor
-                    }
-                }),
-                Direction::Down => request_queue.iter().max_by_key(|r| {
-                    if r.entered {
-                        r.target_floor
-                    } else {
-                        r.current_floor
-                    }
-                }),
-            };
-
-            if let Some(&request) = floor {
-                let target_floor = if request.entered {
-                    request.target_floor
-                } else {
-                    request.current_floor
-                };
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+}
 
-                match self.elevator_current_floor.cmp(&target_floor) {
-                    Ordering::Less => {
-                        println!(
-                            "\tElevator {} move up and stopped at floor {}",
-                            self.id, target_floor
-                        );
-                    }
-                    Ordering::Greater => {
-                        println!(
-                            "\tElevator {} move down and stopped at floor {}",
-                            self.id, target_floor
-                        );
-                    }
-                    Ordering::Equal => {
-                        println!("\tElevator {} stopped at floor {}", self.id, target_floor);
-                    }
-                }
-                // Get all the people that want to enter the lift
-                request_queue
-                    .iter_mut()
-                    .filter(|r| r.current_floor == target_floor)
-                    .for_each(|r| {
-                        r.entered = true;
-                        println!(
-                            "Person {} enters elevator {} at floor {}",
-                            r.person_id, self.id, target_floor
-                        );
-                    });
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
 
-                self.elevator_current_floor = target_floor;
-
-                // Get all the people that want to exit lift
-                let exit_idx = request_queue
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, r)| r.entered && r.target_floor == target_floor)
-                    .map(|(i, _)| i)
-                    .collect::<Vec<_>>();
-
-                for (i, idx) in exit_idx.iter().enumerate() {
-                    println!(
-                        "Person {} exits elevator {} at floor {}",
-                        request_queue[idx - i].person_id,
-                        self.id,
-                        target_floor
-                    );
-                    request_queue.remove(idx - i);
-                }
-                self.elevator_current_floor = target_floor;
-            } else {
-                break;
-            }
+    /// Whether `floor` lies at or beyond `from` when travelling this way.
+    pub fn is_ahead(self, from: usize, floor: usize) -> bool {
+        match self {
+            Direction::Up => floor >= from,
+            Direction::Down => floor <= from,
         }
     }
+}
 
-    pub fn handle_requests(
-        &mut self,
-        request_queue: &VecDeque<ButtonPressed>,
-        request_queue_count: usize,
-    ) -> usize {
-        if let Some(first_request) = request_queue.front() {
-            let queue = request_queue
-                .iter()
-                .take(request_queue_count)
-                .cloned()
-                .collect::<Vec<_>>();
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ElevatorState {
+    Idle,
+    MovingUp,
+    MovingDown,
+    DoorsOpening,
+    DoorsOpen,
+    DoorsClosing,
+    Maintenance,
+    OutOfService,
+    EmergencyStop,
+    /// Parked with its doors open under firefighters' service.
+    FireService,
+}
 
-            let direction = match first_request.current_floor.cmp(&first_request.target_floor) {
-                Ordering::Greater => Direction::Down,
-                Ordering::Less => Direction::Up,
-                Ordering::Equal => unreachable!("Current floor cannot be equal to target floor"),
-            };
+impl ElevatorState {
+    /// Whether a car in this state may go directly into `next`.
+    ///
+    /// Staying in the same state is always allowed. An emergency stop can be
+    /// triggered from anywhere, but a car only leaves it by being reset to
+    /// idle, rescued at a floor, or taken out of service.
+    pub fn can_transition_to(self, next: ElevatorState) -> bool {
+        use ElevatorState::*;
 
-            self.move_elevator(queue, direction);
-        } else {
-            println!("****ERROR: handle request error, request queue is empty");
+        if self == next || next == EmergencyStop {
+            return true;
         }
 
-        self.elevator_current_floor
+        match self {
+            Idle => matches!(
+                next,
+                MovingUp | MovingDown | DoorsOpening | Maintenance | OutOfService
+            ),
+            MovingUp | MovingDown => matches!(next, DoorsOpening | Idle | OutOfService),
+            DoorsOpening => matches!(next, DoorsOpen | DoorsClosing),
+            DoorsOpen => matches!(
+                next,
+                DoorsClosing | Maintenance | OutOfService | FireService
+            ),
+            DoorsClosing => matches!(next, Idle | MovingUp | MovingDown | DoorsOpening),
+            Maintenance => matches!(next, Idle | OutOfService),
+            OutOfService => matches!(next, Idle | Maintenance),
+            EmergencyStop => matches!(next, Idle | DoorsOpening | OutOfService),
+            FireService => matches!(next, DoorsClosing | OutOfService),
+        }
     }
 }
 
-pub fn elevator_process_request(
-    elevator_name: &str,
-    elevator_current_floor: &Arc<Mutex<usize>>,
-    button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
-    elevator_requests_queue: &Mutex<VecDeque<ButtonPressed>>,
-    elevator_request_s: &mpsc::Sender<QueueStatus>,
-    complete_receiving_buttons: &Arc<Mutex<bool>>,
-    complete: &Arc<Mutex<bool>>,
-) {
-    let elevator = Elevator::new_elevator(
-        elevator_name.to_string(),
-        *elevator_current_floor.lock().unwrap(),
-    );
-    if let Some(request_queue) = elevator.process_requests(button_press_queue.lock().unwrap()) {
-        let mut elevator_requests_queue = elevator_requests_queue.lock().unwrap();
-        let request_queue_count = request_queue.len();
-        elevator_requests_queue.extend(request_queue);
+impl fmt::Display for ElevatorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
 
-        elevator_request_s
-            .send(QueueStatus::NewQueue(request_queue_count))
-            .unwrap();
-    } else {
-        match *complete_receiving_buttons.lock().unwrap() {
-            true => {
-                if !(*complete.lock().unwrap()) {
-                    elevator_request_s.send(QueueStatus::Done).unwrap();
-                    *complete.lock().unwrap() = true;
-                } else {
-                    elevator_request_s.send(QueueStatus::Empty).unwrap()
-                }
-            }
-            false => {
-                // println!("Done message already sent!");
-            }
-        }
+#[derive(Debug, PartialEq)]
+pub struct StateTransitionError {
+    pub elevator_id: String,
+    pub from: ElevatorState,
+    pub to: ElevatorState,
+}
+
+impl fmt::Display for StateTransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "elevator {} cannot go from {} to {}",
+            self.elevator_id, self.from, self.to
+        )
     }
 }
 
-pub fn elevator_handle_request(
-    elevator_name: &str,
-    elevator_request_r: &Receiver<QueueStatus>,
-    elevator_requests_queue: &Mutex<VecDeque<ButtonPressed>>,
-    current_floor: &Arc<Mutex<usize>>,
-    elevator_2_finish_s: &crossbeam_channel::Sender<()>,
-    handle: &JobHandle,
-) -> bool {
-    let mut elevator =
-        Elevator::new_elevator(elevator_name.to_string(), *current_floor.lock().unwrap());
-    if let Ok(queue_status) = elevator_request_r.recv() {
-        match queue_status {
-            QueueStatus::NewQueue(request_queue_count) => {
-                let mut request_queue = elevator_requests_queue.lock().unwrap();
-                println!(
-                    "\tElevator {} handle request of person {:?}",
-                    elevator.id,
-                    request_queue
-                        .iter()
-                        .map(|r| r.person_id)
-                        .collect::<Vec<_>>()
-                );
-                let elevator_current_floor =
-                    elevator.handle_requests(&request_queue, request_queue_count);
+impl std::error::Error for StateTransitionError {}
+
+/// Why a call or command was turned away at intake.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RequestError {
+    /// The person is already on the floor they want to go to.
+    SameFloor {
+        person_id: usize,
+        floor: usize,
+    },
+    FloorOutOfRange {
+        floor: usize,
+        floors: usize,
+    },
+    UnknownElevator(String),
+    /// Calls are cancelled while the cars are recalled for fire service.
+    FireRecall,
+    /// The elevator is not parked for, or operated under, fire service.
+    NotInFireService(String),
+}
 
-                *request_queue = request_queue.split_off(request_queue_count);
-                *current_floor.lock().unwrap() = elevator_current_floor;
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::SameFloor { person_id, floor } => {
+                write!(
+                    f,
+                    "person {person_id} wants to go from floor {floor} to itself"
+                )
             }
-            QueueStatus::Empty => {}
-            QueueStatus::Done => {
-                handle.cancel();
-                elevator_2_finish_s.send(()).unwrap();
-                return true;
+            RequestError::FloorOutOfRange { floor, floors } => {
+                write!(f, "floor {floor} is not in a building with {floors} floors")
             }
-        }
+            RequestError::UnknownElevator(elevator_id) => {
+                write!(f, "there is no elevator {elevator_id}")
+            }
+            RequestError::FireRecall => {
+                write!(f, "the elevators are recalled for fire service")
+            }
+            RequestError::NotInFireService(elevator_id) => {
+                write!(f, "elevator {elevator_id} is not under fire service")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// Anything that can go wrong while a fleet runs.
+#[derive(Debug)]
+pub enum ElevatorError {
+    /// A thread panicked while holding the named lock.
+    LockPoisoned(&'static str),
+    /// The other end of the named channel is gone.
+    ChannelClosed(&'static str),
+    /// A message could not be encoded or decoded.
+    Message(serde_json::Error),
+    Transition(StateTransitionError),
+    Request(RequestError),
+    /// A car's worker thread panicked with this message.
+    WorkerPanicked(String),
+    /// An injected fault played out in a car.
+    Fault(Fault),
+    /// Several cars failed while the controller dealt with all of them.
+    Several(Vec<ElevatorError>),
+}
+
+/// What the runtime does about an `ElevatorError`, from mildest to worst.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Recovery {
+    /// Drop whatever failed and carry on with the next tick or message.
+    Retry,
+    /// Take the car out of service; the rest of the fleet keeps running.
+    IsolateCar,
+    /// Stop taking calls and let every car wind down.
+    Shutdown,
+}
+
+impl ElevatorError {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            ElevatorError::Message(_) | ElevatorError::Request(_) => Recovery::Retry,
+            // The car's state can no longer be trusted
+            ElevatorError::LockPoisoned(_)
+            | ElevatorError::Transition(_)
+            | ElevatorError::WorkerPanicked(_)
+            | ElevatorError::Fault(_) => Recovery::IsolateCar,
+            ElevatorError::ChannelClosed(_) => Recovery::Shutdown,
+            ElevatorError::Several(errors) => errors
+                .iter()
+                .map(ElevatorError::recovery)
+                .max()
+                .unwrap_or(Recovery::Retry),
+        }
+    }
+
+    /// The errors among `results`, as one error if there are several.
+    pub fn collect(
+        results: impl IntoIterator<Item = Result<(), ElevatorError>>,
+    ) -> Result<(), ElevatorError> {
+        let mut errors = results
+            .into_iter()
+            .filter_map(Result::err)
+            .collect::<Vec<_>>();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(ElevatorError::Several(errors)),
+        }
+    }
+
+    /// The error for a worker that panicked with `panic`, as caught by
+    /// `catch_unwind`.
+    pub fn worker_panicked(panic: Box<dyn Any + Send>) -> Self {
+        let message = panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default();
+        ElevatorError::WorkerPanicked(message)
+    }
+}
+
+impl fmt::Display for ElevatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElevatorError::LockPoisoned(name) => {
+                write!(f, "{name} lock poisoned by a panicked thread")
+            }
+            ElevatorError::ChannelClosed(name) => write!(f, "{name} channel closed"),
+            ElevatorError::Message(e) => write!(f, "bad message: {e}"),
+            ElevatorError::Transition(e) => e.fmt(f),
+            ElevatorError::Request(e) => e.fmt(f),
+            ElevatorError::WorkerPanicked(message) => write!(f, "worker panicked: {message}"),
+            ElevatorError::Fault(fault) => fault.fmt(f),
+            ElevatorError::Several(errors) => {
+                let messages = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{}", messages.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ElevatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ElevatorError::Message(e) => Some(e),
+            ElevatorError::Transition(e) => Some(e),
+            ElevatorError::Request(e) => Some(e),
+            ElevatorError::LockPoisoned(_)
+            | ElevatorError::ChannelClosed(_)
+            | ElevatorError::WorkerPanicked(_)
+            | ElevatorError::Fault(_)
+            | ElevatorError::Several(_) => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ElevatorError {
+    fn from(e: serde_json::Error) -> Self {
+        ElevatorError::Message(e)
+    }
+}
+
+impl From<StateTransitionError> for ElevatorError {
+    fn from(e: StateTransitionError) -> Self {
+        ElevatorError::Transition(e)
+    }
+}
+
+impl From<RequestError> for ElevatorError {
+    fn from(e: RequestError) -> Self {
+        ElevatorError::Request(e)
+    }
+}
+
+/// Lock `mutex`, naming it in the error if a thread panicked while holding it.
+pub fn lock<'a, T>(
+    mutex: &'a Mutex<T>,
+    name: &'static str,
+) -> Result<MutexGuard<'a, T>, ElevatorError> {
+    mutex.lock().map_err(|_| ElevatorError::LockPoisoned(name))
+}
+
+impl Elevator {
+    pub fn new_elevator(elevator_id: String, current_floor: usize) -> Self {
+        let mut elevator = Self::with_config(elevator_id, ElevatorConfig::default());
+        elevator.elevator_current_floor = current_floor;
+        elevator
+    }
+
+    /// A car built to `config`, waiting at its home floor.
+    pub fn with_config(elevator_id: String, config: ElevatorConfig) -> Self {
+        Elevator {
+            id: elevator_id,
+            elevator_current_floor: config.home_floor.unwrap_or(0),
+            capacity: config.capacity(),
+            status: ElevatorState::Idle,
+            direction: None,
+            passengers: Vec::new(),
+            requests: Vec::new(),
+            strategy: Arc::new(DirectionalBatchDispatch),
+            cost_model: config.cost_model(),
+            config,
+            clock: Clock::wall(),
+            busy_until: Duration::ZERO,
+            trip: None,
+            completed: Vec::new(),
+            transfers: Vec::new(),
+            peak_load: 0,
+            times_full: 0,
+            lamps: Arc::new(Mutex::new(CallLamps::new())),
+            maintenance: Arc::new(Mutex::new(MaintenanceRegistry::new())),
+            power_outage: Arc::new(Mutex::new(false)),
+            fire_service: Arc::new(Mutex::new(FireService::new())),
+            faults: CarFaults::default(),
+            events: Arc::new(ConsoleSink),
+        }
+    }
+
+    /// Move the car into `next`, rejecting transitions the controller must never make.
+    pub fn transition(&mut self, next: ElevatorState) -> Result<(), StateTransitionError> {
+        if self.status.can_transition_to(next) {
+            self.status = next;
+            Ok(())
+        } else {
+            Err(StateTransitionError {
+                elevator_id: self.id.clone(),
+                from: self.status,
+                to: next,
+            })
+        }
+    }
+
+    pub fn door_open(&self) -> bool {
+        matches!(
+            self.status,
+            ElevatorState::DoorsOpening
+                | ElevatorState::DoorsOpen
+                | ElevatorState::DoorsClosing
+                | ElevatorState::FireService
+        )
+    }
+
+    /// Number of passengers currently inside the car.
+    pub fn load(&self) -> usize {
+        self.passengers.len()
+    }
+
+    /// Where the car is at building time `now`, in floors.
+    ///
+    /// `elevator_current_floor` jumps to the next stop as soon as the car
+    /// sets off; this follows the car along its trip instead.
+    pub fn position_at(&self, now: Duration) -> f64 {
+        let Some(trip) = self.trip else {
+            return self.elevator_current_floor as f64;
+        };
+        let floors = trip.from_floor.abs_diff(trip.to_floor);
+        let travelled = self
+            .cost_model
+            .floors_at(floors, now.saturating_sub(trip.departed_at));
+
+        if trip.to_floor > trip.from_floor {
+            trip.from_floor as f64 + travelled
+        } else {
+            trip.from_floor as f64 - travelled
+        }
+    }
+
+    pub fn position(&self) -> f64 {
+        self.position_at(self.clock.now())
+    }
+
+    pub fn snapshot(&self) -> Result<ElevatorSnapshot, ElevatorError> {
+        Ok(ElevatorSnapshot {
+            id: self.id.clone(),
+            elevator_current_floor: self.elevator_current_floor,
+            capacity: self.capacity,
+            status: self.status,
+            direction: self.direction,
+            door_open: self.door_open(),
+            position: self.position(),
+            load: self.load(),
+            passengers: self.passengers.iter().map(|p| p.person_id).collect(),
+            car_calls: lock(&self.lamps, "call lamps")?
+                .car_calls(&self.id)
+                .collect(),
+        })
+    }
+
+    /// Find all users to fetch, using the car's dispatch strategy.
+    ///
+    /// Calls to or from a floor the car does not serve stay in the queue for
+    /// another car.
+    pub fn process_requests(
+        &self,
+        mut queue: MutexGuard<VecDeque<ButtonPressed>>,
+    ) -> Option<VecDeque<ButtonPressed>> {
+        if self.config.served_floors.is_empty() {
+            return self.strategy.select_requests(self, &mut queue);
+        }
+
+        let all = queue.drain(..).collect::<Vec<_>>();
+        let mut servable = all
+            .iter()
+            .filter(|r| self.config.serves_trip(r))
+            .copied()
+            .collect::<VecDeque<_>>();
+        let selected = self.strategy.select_requests(self, &mut servable);
+
+        // Put back everything not selected, in its original order
+        let selected_ids = selected
+            .iter()
+            .flatten()
+            .map(|r| r.person_id)
+            .collect::<Vec<_>>();
+        queue.extend(
+            all.into_iter()
+                .filter(|r| !selected_ids.contains(&r.person_id)),
+        );
+
+        selected
+    }
+
+    pub fn move_elevator(
+        &mut self,
+        request_queue: Vec<ButtonPressed>,
+        direction: Direction,
+        button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
+    ) -> Result<(), ElevatorError> {
+        self.assign_requests(request_queue, direction);
+        // A car taken out of service stops after the stop it is making
+        while self.in_service()? && self.step(button_press_queue)?.is_some() {}
+
+        Ok(())
+    }
+
+    /// Whether the car takes calls: it is not under maintenance, the
+    /// building has power and the cars are not recalled for a fire.
+    pub fn in_service(&self) -> Result<bool, ElevatorError> {
+        Ok(!lock(&self.maintenance, "maintenance")?.contains(&self.id)
+            && !*lock(&self.power_outage, "power outage")?
+            && !lock(&self.fire_service, "fire service")?.is_active())
+    }
+
+    /// Stop at the floor the car is at or heading for and put it into
+    /// maintenance, letting everyone inside out.
+    ///
+    /// During a power failure the car instead switches to battery, creeps to
+    /// the nearest floor or its evacuation floor, and waits there with its
+    /// doors open for the power to come back. On a fire recall, with power,
+    /// it goes to the recall floor; see `recall`.
+    ///
+    /// Returns the riders, as new calls from that floor, and the calls the
+    /// car took but did not pick up yet.
+    pub fn evacuate(&mut self) -> Result<(Vec<ButtonPressed>, Vec<ButtonPressed>), ElevatorError> {
+        let recall_floor = lock(&self.fire_service, "fire service")?.recall_floor();
+        if let Some(floor) = recall_floor.filter(|_| !self.power_failed()) {
+            self.recall(floor)?;
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let events = Arc::clone(&self.events);
+        let (riders, waiting): (Vec<_>, Vec<_>) =
+            self.requests.drain(..).partition(|request| request.entered);
+        let waiting = waiting
+            .into_iter()
+            .map(|request| ButtonPressed {
+                assigned_at: None,
+                ..request
+            })
+            .collect();
+        self.direction = None;
+        if matches!(
+            self.status,
+            ElevatorState::Maintenance | ElevatorState::EmergencyStop
+        ) {
+            // Parked already; anyone inside a stuck car waits to be rescued
+            self.requests = riders;
+            return Ok((Vec::new(), waiting));
+        }
+
+        let parked = if self.power_failed() {
+            self.run_on_battery()?;
+            ElevatorState::EmergencyStop
+        } else {
+            ElevatorState::Maintenance
+        };
+        let floor = self.elevator_current_floor;
+        let calls = self.let_riders_out(&riders)?;
+
+        self.transition(parked)?;
+        events.publish(
+            self.clock.now().max(self.busy_until),
+            &SimEvent::CarEvacuated {
+                elevator_id: self.id.clone(),
+                floor,
+                person_ids: riders.iter().map(|rider| rider.person_id).collect(),
+            },
+        );
+
+        Ok((calls, waiting))
+    }
+
+    /// Open the doors where the car is, unless it is idle and empty or they
+    /// are open already, and let `riders` out. Returns them as new calls from
+    /// there.
+    fn let_riders_out(
+        &mut self,
+        riders: &[ButtonPressed],
+    ) -> Result<Vec<ButtonPressed>, ElevatorError> {
+        let events = Arc::clone(&self.events);
+        let floor = self.elevator_current_floor;
+        let mut calls = Vec::new();
+        if self.status != ElevatorState::Idle || !riders.is_empty() {
+            let arrived_at = self.clock.now().max(self.busy_until);
+            // Out through doors that are open already, such as jammed ones
+            let mut doors_open_at = self.clock.now();
+            if self.status != ElevatorState::DoorsOpen {
+                // Between stops: open the doors again where the car stops next
+                doors_open_at = arrived_at + self.cost_model.door_open_time;
+                self.transition(ElevatorState::DoorsOpening)?;
+                self.transition(ElevatorState::DoorsOpen)?;
+                events.publish(
+                    doors_open_at,
+                    &SimEvent::DoorsOpened {
+                        elevator_id: self.id.clone(),
+                        floor,
+                    },
+                );
+            }
+
+            for rider in riders {
+                events.publish(
+                    doors_open_at,
+                    &SimEvent::PassengerAlighted {
+                        elevator_id: self.id.clone(),
+                        person_id: rider.person_id,
+                        floor,
+                    },
+                );
+                if rider.target_floor == floor || rider.destination == Some(floor) {
+                    // Let out where they were going anyway
+                    self.finish_leg(*rider, floor, doors_open_at);
+                } else {
+                    let mut call =
+                        ButtonPressed::new_request(rider.person_id, floor, rider.target_floor);
+                    call.destination = rider.destination;
+                    call.registered_at = rider.registered_at;
+                    call.transferred_at = Some(doors_open_at);
+                    calls.push(call);
+                }
+
+                let car_call = rider.car_call(&self.id);
+                if lock(&self.lamps, "call lamps")?.answer_car(&car_call) {
+                    events.publish(
+                        doors_open_at,
+                        &SimEvent::CarCallCancelled { call: car_call },
+                    );
+                }
+            }
+            self.passengers.clear();
+            self.busy_until = arrived_at + self.cost_model.stop_time(riders.len());
+        }
+
+        Ok(calls)
+    }
+
+    /// Record `request` as having got off at `floor` at `at`. Someone only
+    /// changing cars there carries on with another car.
+    fn finish_leg(&mut self, mut request: ButtonPressed, floor: usize, at: Duration) {
+        request.alighted_at = Some(at);
+        self.completed.push(request);
+
+        if let Some(destination) = request.destination.filter(|d| *d != floor) {
+            let mut next_leg = ButtonPressed::new_request(request.person_id, floor, destination);
+            next_leg.registered_at = request.registered_at;
+            next_leg.transferred_at = Some(at);
+            self.transfers.push(next_leg);
+        }
+    }
+
+    /// Stop dead where the car is, between floors if it is moving, with
+    /// everyone inside.
+    pub fn stick(&mut self) -> Result<(), StateTransitionError> {
+        let position = self.position();
+        self.transition(ElevatorState::EmergencyStop)?;
+        self.direction = None;
+        self.faults.stuck = true;
+        self.events.publish(
+            self.clock.now(),
+            &SimEvent::CarStuck {
+                elevator_id: self.id.clone(),
+                position,
+            },
+        );
+        Ok(())
+    }
+
+    /// Free a stuck car: level it at the floor it was heading for and let
+    /// everyone out. Returns the riders, as new calls from that floor.
+    pub fn rescue(&mut self) -> Result<Vec<ButtonPressed>, ElevatorError> {
+        if !std::mem::take(&mut self.faults.stuck) {
+            return Ok(Vec::new());
+        }
+
+        let (riders, waiting): (Vec<_>, Vec<_>) =
+            self.requests.drain(..).partition(|request| request.entered);
+        self.requests = waiting;
+        let calls = self.let_riders_out(&riders)?;
+        self.transition(ElevatorState::DoorsClosing)?;
+        self.events.publish(
+            self.busy_until,
+            &SimEvent::DoorsClosed {
+                elevator_id: self.id.clone(),
+                floor: self.elevator_current_floor,
+            },
+        );
+        self.transition(ElevatorState::Idle)?;
+        self.events.publish(
+            self.busy_until,
+            &SimEvent::CarRescued {
+                elevator_id: self.id.clone(),
+                floor: self.elevator_current_floor,
+                person_ids: riders.iter().map(|rider| rider.person_id).collect(),
+            },
+        );
+        Ok(calls)
+    }
+
+    fn power_failed(&self) -> bool {
+        // A poisoned flag is reported by `in_service`; assume the worst
+        self.power_outage
+            .lock()
+            .map_or(true, |power_outage| *power_outage)
+    }
+
+    /// Phase I of fire service: cancel the car's calls, take it non-stop to
+    /// `floor`, or the served floor closest to it, let everyone out and park
+    /// there with the doors open.
+    pub fn recall(&mut self, floor: usize) -> Result<(), ElevatorError> {
+        let events = Arc::clone(&self.events);
+        let now = self.clock.now();
+        let (riders, waiting): (Vec<_>, Vec<_>) =
+            self.requests.drain(..).partition(|request| request.entered);
+        for request in waiting {
+            events.publish(now, &SimEvent::CallCancelled { request });
+        }
+        self.direction = None;
+        // Cars under maintenance stay where they are, and anyone inside a
+        // stuck car waits there to be rescued
+        if matches!(
+            self.status,
+            ElevatorState::FireService | ElevatorState::Maintenance | ElevatorState::EmergencyStop
+        ) {
+            self.requests = riders;
+            return Ok(());
+        }
+
+        let floor = self.config.closest_served(floor);
+        let doors_open_at = self.travel_non_stop(floor)?;
+        for rider in &riders {
+            events.publish(
+                doors_open_at,
+                &SimEvent::PassengerAlighted {
+                    elevator_id: self.id.clone(),
+                    person_id: rider.person_id,
+                    floor,
+                },
+            );
+            let car_call = rider.car_call(&self.id);
+            if lock(&self.lamps, "call lamps")?.answer_car(&car_call) {
+                events.publish(
+                    doors_open_at,
+                    &SimEvent::CarCallCancelled { call: car_call },
+                );
+            }
+        }
+        self.passengers.clear();
+
+        self.transition(ElevatorState::FireService)?;
+        events.publish(
+            doors_open_at,
+            &SimEvent::CarRecalled {
+                elevator_id: self.id.clone(),
+                floor,
+            },
+        );
+        Ok(())
+    }
+
+    /// Phase II of fire service: answer the firefighter's car call to
+    /// `floor`, running non-stop and parking there with the doors open.
+    pub fn firefighter_trip(&mut self, floor: usize) -> Result<(), ElevatorError> {
+        let events = Arc::clone(&self.events);
+        if self.status != ElevatorState::FireService {
+            return Err(RequestError::NotInFireService(self.id.clone()).into());
+        }
+
+        let call = CarCall {
+            car: self.id.clone(),
+            floor,
+        };
+        if lock(&self.lamps, "call lamps")?.press_car(call.clone()) {
+            events.publish(
+                self.clock.now(),
+                &SimEvent::CarCallLit { call: call.clone() },
+            );
+        }
+        let doors_open_at = self.travel_non_stop(floor)?;
+        if lock(&self.lamps, "call lamps")?.answer_car(&call) {
+            events.publish(doors_open_at, &SimEvent::CarCallCancelled { call });
+        }
+        self.transition(ElevatorState::FireService)?;
+        Ok(())
+    }
+
+    /// Close the doors if they are open and run straight to `floor`, passing
+    /// every call on the way, then open the doors there. Returns when they
+    /// are open.
+    fn travel_non_stop(&mut self, floor: usize) -> Result<Duration, StateTransitionError> {
+        let events = Arc::clone(&self.events);
+        let from_floor = self.elevator_current_floor;
+        if matches!(
+            self.status,
+            ElevatorState::DoorsOpening | ElevatorState::DoorsOpen | ElevatorState::FireService
+        ) {
+            self.transition(ElevatorState::DoorsClosing)?;
+            self.busy_until =
+                self.clock.now().max(self.busy_until) + self.cost_model.door_close_time;
+            events.publish(
+                self.busy_until,
+                &SimEvent::DoorsClosed {
+                    elevator_id: self.id.clone(),
+                    floor: from_floor,
+                },
+            );
+        }
+
+        let mut arrived_at = self.clock.now().max(self.busy_until);
+        if floor != from_floor {
+            let moving = if floor > from_floor {
+                self.transition(ElevatorState::MovingUp)?;
+                Direction::Up
+            } else {
+                self.transition(ElevatorState::MovingDown)?;
+                Direction::Down
+            };
+            let trip = self.depart(floor);
+            arrived_at = trip.departed_at + trip.travel_time;
+            events.publish(
+                arrived_at,
+                &SimEvent::CarArrived {
+                    elevator_id: self.id.clone(),
+                    floor,
+                    direction: Some(moving),
+                },
+            );
+        }
+
+        let doors_open_at = arrived_at + self.cost_model.door_open_time;
+        self.transition(ElevatorState::DoorsOpening)?;
+        self.transition(ElevatorState::DoorsOpen)?;
+        events.publish(
+            doors_open_at,
+            &SimEvent::DoorsOpened {
+                elevator_id: self.id.clone(),
+                floor,
+            },
+        );
+        self.busy_until = doors_open_at;
+        Ok(doors_open_at)
+    }
+
+    /// Leave fire service: close the doors and wait for calls again.
+    pub fn end_fire_service(&mut self) -> Result<(), StateTransitionError> {
+        if self.status != ElevatorState::FireService {
+            return Ok(());
+        }
+        self.transition(ElevatorState::DoorsClosing)?;
+        self.busy_until = self.clock.now().max(self.busy_until) + self.cost_model.door_close_time;
+        self.events.publish(
+            self.busy_until,
+            &SimEvent::DoorsClosed {
+                elevator_id: self.id.clone(),
+                floor: self.elevator_current_floor,
+            },
+        );
+        self.transition(ElevatorState::Idle)
+    }
+
+    /// Cut to battery and stop at the nearest floor, or go on to the
+    /// evacuation floor, at battery speed.
+    fn run_on_battery(&mut self) -> Result<(), StateTransitionError> {
+        let now = self.clock.now();
+        let position = self.position_at(now);
+        let mid_trip = self
+            .trip
+            .is_some_and(|trip| now < trip.departed_at + trip.travel_time);
+        self.transition(ElevatorState::EmergencyStop)?;
+
+        let floor = self
+            .config
+            .evacuation_floor
+            .unwrap_or(position.round() as usize);
+        let distance = (floor as f64 - position).abs();
+        if distance < f64::EPSILON {
+            return Ok(());
+        }
+
+        // A moving car slows down from where it is; a stopped one first
+        // finishes the stop it is making
+        let departed_at = if mid_trip {
+            now
+        } else {
+            now.max(self.busy_until)
+        };
+        self.elevator_current_floor = floor;
+        self.trip = None;
+        self.busy_until = departed_at + self.cost_model.battery_travel_time(distance);
+        self.events.publish(
+            self.busy_until,
+            &SimEvent::CarArrived {
+                elevator_id: self.id.clone(),
+                floor,
+                direction: Some(if floor as f64 > position {
+                    Direction::Up
+                } else {
+                    Direction::Down
+                }),
+            },
+        );
+        Ok(())
+    }
+
+    /// Leave maintenance, ready to take calls again. A car whose floor
+    /// sensor misread first runs to find where it is.
+    pub fn return_to_service(&mut self) -> Result<(), StateTransitionError> {
+        if self.status == ElevatorState::Maintenance {
+            self.transition(ElevatorState::Idle)?;
+            if self.faults.misreported_floor.take().is_some() {
+                self.correction_run()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Leave battery operation once the power is back. A stuck car stays
+    /// stopped until it is rescued.
+    pub fn restore_power(&mut self) -> Result<(), StateTransitionError> {
+        if self.status == ElevatorState::EmergencyStop && !self.faults.stuck {
+            self.transition(ElevatorState::Idle)?;
+        }
+        Ok(())
+    }
+
+    /// Add `request_queue` to the current trip without moving the car yet.
+    pub fn assign_requests(&mut self, request_queue: Vec<ButtonPressed>, direction: Direction) {
+        let now = self.clock.now();
+        self.requests
+            .extend(request_queue.into_iter().map(|mut request| {
+                request.assigned_at.get_or_insert(now);
+                request
+            }));
+        self.direction = Some(direction);
+    }
+
+    /// Travel to the next stop of the current trip and let people on and off.
+    ///
+    /// Returns the simulated time the stop took, or `None` once the trip is
+    /// over and the car is idle again.
+    pub fn step(
+        &mut self,
+        button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
+    ) -> Result<Option<Duration>, ElevatorError> {
+        let strategy = Arc::clone(&self.strategy);
+        let events = Arc::clone(&self.events);
+        if std::mem::take(&mut self.faults.worker_panic) {
+            panic!("injected fault in the worker of elevator {}", self.id);
+        }
+        if let Some(jam) = self.faults.jammed.take() {
+            return Err(ElevatorError::Fault(Fault::DoorJam {
+                elevator_id: self.id.clone(),
+                floor: jam.floor,
+                duration_ms: jam.duration.as_millis() as u64,
+            }));
+        }
+        if let Some(reported_floor) = self.faults.misreported_floor {
+            let floor = self.elevator_current_floor;
+            events.publish(
+                self.clock.now().max(self.busy_until),
+                &SimEvent::SensorFault {
+                    elevator_id: self.id.clone(),
+                    reported_floor,
+                    floor,
+                },
+            );
+            // The car no longer knows where it is; it finds out again once
+            // it is back in service
+            if reported_floor != floor {
+                return Err(ElevatorError::Fault(Fault::WrongFloor {
+                    elevator_id: self.id.clone(),
+                    reported_floor,
+                }));
+            }
+            self.faults.misreported_floor = None;
+        }
+        let direction = self.direction.unwrap_or(Direction::Up);
+        let Some((target_floor, mut direction)) =
+            strategy.next_stop(self, &self.requests, direction)
+        else {
+            self.direction = None;
+
+            // Nothing left to do: head back to the home floor, if there is one
+            if let Some(home_floor) = self
+                .config
+                .home_floor
+                .filter(|home_floor| *home_floor != self.elevator_current_floor)
+            {
+                let moving = if home_floor > self.elevator_current_floor {
+                    self.transition(ElevatorState::MovingUp)?;
+                    Direction::Up
+                } else {
+                    self.transition(ElevatorState::MovingDown)?;
+                    Direction::Down
+                };
+                let trip = self.depart(home_floor);
+                self.busy_until = trip.departed_at + trip.travel_time;
+                events.publish(
+                    self.busy_until,
+                    &SimEvent::CarArrived {
+                        elevator_id: self.id.clone(),
+                        floor: home_floor,
+                        direction: Some(moving),
+                    },
+                );
+                self.transition(ElevatorState::Idle)?;
+                return Ok(Some(trip.travel_time));
+            }
+
+            self.transition(ElevatorState::Idle)?;
+            events.publish(
+                self.clock.now().max(self.busy_until),
+                &SimEvent::CarIdle {
+                    elevator_id: self.id.clone(),
+                },
+            );
+            return Ok(None);
+        };
+
+        let moving = match self.elevator_current_floor.cmp(&target_floor) {
+            Ordering::Less => {
+                self.transition(ElevatorState::MovingUp)?;
+                Some(Direction::Up)
+            }
+            Ordering::Greater => {
+                self.transition(ElevatorState::MovingDown)?;
+                Some(Direction::Down)
+            }
+            Ordering::Equal => None,
+        };
+        let trip = self.depart(target_floor);
+        let arrived_at = trip.departed_at + trip.travel_time;
+        let doors_open_at = arrived_at + self.cost_model.door_open_time;
+        events.publish(
+            arrived_at,
+            &SimEvent::CarArrived {
+                elevator_id: self.id.clone(),
+                floor: target_floor,
+                direction: moving,
+            },
+        );
+        self.transition(ElevatorState::DoorsOpening)?;
+        self.transition(ElevatorState::DoorsOpen)?;
+        events.publish(
+            doors_open_at,
+            &SimEvent::DoorsOpened {
+                elevator_id: self.id.clone(),
+                floor: target_floor,
+            },
+        );
+
+        // Work out which way the car leaves this floor once riders for
+        // it got off, so it answers calls going that way
+        let remaining = self
+            .requests
+            .iter()
+            .filter(|r| !(r.entered && r.target_floor == target_floor))
+            .copied()
+            .collect::<Vec<_>>();
+        if let Some((_, leaving)) = strategy.next_stop(self, &remaining, direction) {
+            direction = leaving;
+        }
+        self.direction = Some(direction);
+
+        // Pick up any new calls on the way before the doors close
+        let en_route = strategy.collect_en_route(
+            self,
+            &self.requests,
+            direction,
+            &mut *lock(button_press_queue, "hall calls")?,
+        );
+        self.requests
+            .extend(en_route.into_iter().map(|mut request| {
+                request.assigned_at.get_or_insert(arrived_at);
+                request
+            }));
+
+        // Get all the people that want to exit lift, making room for new ones
+        let exit_idx = self
+            .requests
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.entered && r.target_floor == target_floor)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let car_call = CarCall {
+            car: self.id.clone(),
+            floor: target_floor,
+        };
+        if lock(&self.lamps, "call lamps")?.answer_car(&car_call) {
+            events.publish(
+                doors_open_at,
+                &SimEvent::CarCallCancelled { call: car_call },
+            );
+        }
+        for (i, idx) in exit_idx.iter().enumerate() {
+            let request = self.requests.remove(idx - i);
+            events.publish(
+                doors_open_at,
+                &SimEvent::PassengerAlighted {
+                    elevator_id: self.id.clone(),
+                    person_id: request.person_id,
+                    floor: target_floor,
+                },
+            );
+            self.passengers.retain(|p| p.person_id != request.person_id);
+            self.finish_leg(request, target_floor, doors_open_at);
+        }
+
+        // Get all the people that want to enter the lift, as long as they fit
+        let mut left_behind = Vec::new();
+        let mut boarded = 0;
+        for r in self.requests.iter_mut().filter(|r| {
+            !r.entered && r.current_floor == target_floor && strategy.should_board(r, direction)
+        }) {
+            if self.passengers.len() >= self.capacity {
+                left_behind.push(r.person_id);
+                continue;
+            }
+            r.entered = true;
+            r.boarded_at = Some(doors_open_at);
+            self.passengers.push(*r);
+            boarded += 1;
+            events.publish(
+                doors_open_at,
+                &SimEvent::PassengerBoarded {
+                    elevator_id: self.id.clone(),
+                    person_id: r.person_id,
+                    floor: target_floor,
+                },
+            );
+
+            let mut lamps = lock(&self.lamps, "call lamps")?;
+            let hall_call = r.hall_call();
+            if lamps.answer_hall(hall_call, r.person_id) {
+                events.publish(
+                    doors_open_at,
+                    &SimEvent::HallCallCancelled { call: hall_call },
+                );
+            }
+            let car_call = r.car_call(&self.id);
+            if lamps.press_car(car_call.clone()) {
+                events.publish(doors_open_at, &SimEvent::CarCallLit { call: car_call });
+            }
+        }
+        self.peak_load = self.peak_load.max(self.load());
+        self.busy_until = arrived_at + self.cost_model.stop_time(exit_idx.len() + boarded);
+        // Whoever did not fit waits for the next car, keeping their place in time
+        if !left_behind.is_empty() {
+            let mut requeued = Vec::new();
+            self.requests.retain(|r| {
+                if !r.entered && left_behind.contains(&r.person_id) {
+                    requeued.push(ButtonPressed {
+                        assigned_at: None,
+                        ..*r
+                    });
+                    return false;
+                }
+                true
+            });
+            lock(button_press_queue, "hall calls")?.extend(requeued);
+            self.times_full += 1;
+            events.publish(
+                doors_open_at,
+                &SimEvent::CarFull {
+                    elevator_id: self.id.clone(),
+                    floor: target_floor,
+                    person_ids: left_behind,
+                },
+            );
+        }
+
+        if let Some(jam) = self
+            .faults
+            .door_jam
+            .take_if(|jam| jam.floor == target_floor)
+        {
+            // The car cannot leave until the doors are freed, and is taken
+            // out as soon as they jam
+            self.busy_until += jam.duration;
+            self.faults.jammed = Some(jam);
+            events.publish(
+                doors_open_at,
+                &SimEvent::DoorsJammed {
+                    elevator_id: self.id.clone(),
+                    floor: target_floor,
+                },
+            );
+            return Ok(Some(doors_open_at - trip.departed_at));
+        }
+
+        self.transition(ElevatorState::DoorsClosing)?;
+        events.publish(
+            self.busy_until,
+            &SimEvent::DoorsClosed {
+                elevator_id: self.id.clone(),
+                floor: target_floor,
+            },
+        );
+
+        Ok(Some(self.busy_until - trip.departed_at))
+    }
+
+    /// The floor sensor disagreed with where the car knows it went last: run
+    /// down to the lowest floor the car serves to find its position again.
+    fn correction_run(&mut self) -> Result<(), StateTransitionError> {
+        let floor = self.elevator_current_floor;
+        let terminal_floor = self.config.closest_served(0);
+        if terminal_floor != floor {
+            self.transition(ElevatorState::MovingDown)?;
+            let trip = self.depart(terminal_floor);
+            self.busy_until = trip.departed_at + trip.travel_time;
+            self.events.publish(
+                self.busy_until,
+                &SimEvent::CarArrived {
+                    elevator_id: self.id.clone(),
+                    floor: terminal_floor,
+                    direction: Some(Direction::Down),
+                },
+            );
+            self.transition(ElevatorState::Idle)?;
+        }
+        Ok(())
+    }
+
+    /// Set off for `to_floor` as soon as the car is free, recording the trip.
+    fn depart(&mut self, to_floor: usize) -> Trip {
+        let trip = Trip {
+            from_floor: self.elevator_current_floor,
+            to_floor,
+            departed_at: self.clock.now().max(self.busy_until),
+            travel_time: self
+                .cost_model
+                .travel_time(self.elevator_current_floor.abs_diff(to_floor)),
+        };
+        self.trip = Some(trip);
+        self.elevator_current_floor = to_floor;
+        trip
+    }
+
+    /// Estimate when this car would reach `request` and deliver it.
+    ///
+    /// The car is assumed to finish the work it is already committed to and the
+    /// calls already `assigned` to it, serving every stop in LOOK order from
+    /// the floor that work leaves it at. Returns `None` if the car would not
+    /// get the person there, so no car is picked on a made-up estimate.
+    pub fn estimated_time_of_arrival(
+        &self,
+        assigned: &[ButtonPressed],
+        request: &ButtonPressed,
+    ) -> Option<Eta> {
+        let mut requests = self
+            .requests
+            .iter()
+            .chain(assigned)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut candidate = ButtonPressed {
+            entered: false,
+            ..*request
+        };
+        let mut floor = self.elevator_current_floor;
+        let mut direction =
+            self.direction
+                .unwrap_or(if request.current_floor < self.elevator_current_floor {
+                    Direction::Down
+                } else {
+                    Direction::Up
+                });
+        let mut eta = Eta {
+            pick_up: Duration::ZERO,
+            destination: Duration::ZERO,
+            stops: 0,
+        };
+        // The car is free once it is through the stops it committed to, the
+        // trip in progress included; by then it stands at
+        // `elevator_current_floor`, which already holds where that work ends
+        let mut elapsed = self.busy_until.saturating_sub(self.clock.now());
+
+        // Every stop lets somebody on or off, so the person is delivered well
+        // before the bound.
+        for _ in 0..4 * (requests.len() + 2) {
+            let pending = requests
+                .iter()
+                .chain(std::iter::once(&candidate))
+                .copied()
+                .collect::<Vec<_>>();
+            let Some((stop, next_direction)) = sweep_next_stop(floor, &pending, direction, None)
+            else {
+                break;
+            };
+            let arrival = elapsed + self.cost_model.travel_time(floor.abs_diff(stop));
+            elapsed = arrival + self.cost_model.stop_time(1);
+            floor = stop;
+            direction = next_direction;
+            eta.stops += 1;
+
+            if candidate.entered && candidate.target_floor == stop {
+                eta.destination = arrival;
+                return Some(eta);
+            }
+
+            requests.retain(|r| !(r.entered && r.target_floor == stop));
+            for r in requests.iter_mut() {
+                if !r.entered && r.current_floor == stop && r.direction() == direction {
+                    r.entered = true;
+                }
+            }
+            if !candidate.entered
+                && candidate.current_floor == stop
+                && candidate.direction() == direction
+            {
+                candidate.entered = true;
+                eta.pick_up = arrival;
+            }
+        }
+
+        None
+    }
+
+    pub fn handle_requests(
+        &mut self,
+        request_queue: &VecDeque<ButtonPressed>,
+        request_queue_count: usize,
+        button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
+    ) -> Result<usize, ElevatorError> {
+        if let Some(first_request) = request_queue.front() {
+            let queue = request_queue
+                .iter()
+                .take(request_queue_count)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            // A zero-length trip is turned away at intake; should one still get
+            // here, the car just opens its doors at that floor
+            self.move_elevator(queue, first_request.direction(), button_press_queue)?;
+        } else {
+            self.events.publish(
+                self.clock.now(),
+                &SimEvent::Error {
+                    elevator_id: Some(self.id.clone()),
+                    message: "handle request error, request queue is empty".to_string(),
+                },
+            );
+        }
+
+        Ok(self.elevator_current_floor)
+    }
+}
+
+/// Policy deciding which hall calls a car takes from the shared queue.
+///
+/// Strategies are shared between cars and may be swapped while a fleet runs,
+/// so different policies can be compared against the same scenario.
+pub trait DispatchStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Remove the requests `elevator` should serve next from `queue`.
+    fn select_requests(
+        &self,
+        elevator: &Elevator,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Option<VecDeque<ButtonPressed>>;
+
+    /// Next floor to stop at for `requests`, and the direction the car will
+    /// be heading when it leaves that floor.
+    ///
+    /// By default the car keeps `direction` for the whole batch, visiting the
+    /// lowest stop first going up and the highest stop first going down.
+    fn next_stop(
+        &self,
+        _elevator: &Elevator,
+        requests: &[ButtonPressed],
+        direction: Direction,
+    ) -> Option<(usize, Direction)> {
+        let floors = requests.iter().map(ButtonPressed::stop_floor);
+        let floor = match direction {
+            Direction::Up => floors.min(),
+            Direction::Down => floors.max(),
+        };
+        floor.map(|floor| (floor, direction))
+    }
+
+    /// Extra hall calls to take from `queue` while stopped mid-trip.
+    fn collect_en_route(
+        &self,
+        _elevator: &Elevator,
+        _requests: &[ButtonPressed],
+        _direction: Direction,
+        _queue: &mut VecDeque<ButtonPressed>,
+    ) -> Vec<ButtonPressed> {
+        Vec::new()
+    }
+
+    /// Whether a waiting person boards a car about to travel in `direction`.
+    fn should_board(&self, _request: &ButtonPressed, _direction: Direction) -> bool {
+        true
+    }
+}
+
+/// Take the oldest request and batch every later request going the same way
+/// from a floor at or beyond it, up to the car's capacity.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DirectionalBatchDispatch;
+
+impl DispatchStrategy for DirectionalBatchDispatch {
+    fn name(&self) -> &'static str {
+        "batch"
+    }
+
+    fn select_requests(
+        &self,
+        elevator: &Elevator,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Option<VecDeque<ButtonPressed>> {
+        let mut request_queue = VecDeque::new();
+
+        // Pop the first request from the queue
+        if let Some(request) = queue.pop_front() {
+            request_queue.push_back(request);
+
+            let initial_direction = if request.target_floor < request.current_floor {
+                Direction::Down
+            } else {
+                Direction::Up
+            };
+
+            // Check if there are other requests heading in the same direction
+            let mut i = 0;
+            while i < queue.len() {
+                if let Some(next_request) = queue.get(i) {
+                    let next_request_direction =
+                        if next_request.target_floor < next_request.current_floor {
+                            Direction::Down
+                        } else {
+                            Direction::Up
+                        };
+
+                    // Check if the next request is in the same direction
+                    let is_same_direction = initial_direction == next_request_direction;
+
+                    let is_in_direction = match initial_direction {
+                        Direction::Up => next_request.current_floor >= request.current_floor,
+                        Direction::Down => next_request.current_floor <= request.current_floor,
+                    };
+
+                    if is_same_direction && is_in_direction {
+                        if request_queue.len() == elevator.capacity {
+                            break;
+                        } else {
+                            let new_request = queue.remove(i).unwrap();
+                            request_queue.push_back(new_request);
+                            continue;
+                        }
+                    }
+                }
+                i += 1;
+            }
+
+            Some(request_queue)
+        } else {
+            // If no request is found, return None
+            None
+        }
+    }
+}
+
+/// Next stop of a car sweeping in `direction` from `floor`.
+///
+/// Serves the nearest rider destination or same-direction call ahead. With
+/// nothing left ahead, the car turns around at the farthest opposite-direction
+/// call (LOOK), or at `terminal` when given (SCAN), and then sweeps back.
+fn sweep_next_stop(
+    floor: usize,
+    requests: &[ButtonPressed],
+    direction: Direction,
+    terminal: Option<usize>,
+) -> Option<(usize, Direction)> {
+    if requests.is_empty() {
+        return None;
+    }
+
+    for direction in [direction, direction.opposite()] {
+        let nearest = requests
+            .iter()
+            .filter(|r| r.entered || r.direction() == direction)
+            .map(ButtonPressed::stop_floor)
+            .filter(|&f| direction.is_ahead(floor, f));
+        let nearest = match direction {
+            Direction::Up => nearest.min(),
+            Direction::Down => nearest.max(),
+        };
+        if let Some(stop) = nearest {
+            return Some((stop, direction));
+        }
+
+        if let Some(top_floor) = terminal {
+            let end = match direction {
+                Direction::Up => top_floor,
+                Direction::Down => 0,
+            };
+            if end != floor {
+                return Some((end, direction.opposite()));
+            }
+        }
+
+        let turnaround = requests
+            .iter()
+            .filter(|r| !r.entered && r.direction() != direction)
+            .map(|r| r.current_floor)
+            .filter(|&f| direction.is_ahead(floor, f));
+        let turnaround = match direction {
+            Direction::Up => turnaround.max(),
+            Direction::Down => turnaround.min(),
+        };
+        if let Some(stop) = turnaround {
+            return Some((stop, direction.opposite()));
+        }
+    }
+
+    None
+}
+
+/// Take calls waiting ahead of the car that travel the same way, as long as
+/// the car has room for them.
+fn collect_in_direction(
+    elevator: &Elevator,
+    requests: &[ButtonPressed],
+    direction: Direction,
+    queue: &mut VecDeque<ButtonPressed>,
+) -> Vec<ButtonPressed> {
+    let mut collected = Vec::new();
+    let mut i = 0;
+    while i < queue.len() && requests.len() + collected.len() < elevator.capacity {
+        let call = queue[i];
+        if call.direction() == direction
+            && direction.is_ahead(elevator.elevator_current_floor, call.current_floor)
+            && elevator.config.serves_trip(&call)
+        {
+            collected.push(queue.remove(i).unwrap());
+        } else {
+            i += 1;
+        }
+    }
+    collected
+}
+
+/// LOOK: sweep one way, pick up same-direction calls on the way, and reverse
+/// at the last pending stop.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LookDispatch;
+
+impl DispatchStrategy for LookDispatch {
+    fn name(&self) -> &'static str {
+        "look"
+    }
+
+    fn select_requests(
+        &self,
+        elevator: &Elevator,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Option<VecDeque<ButtonPressed>> {
+        DirectionalBatchDispatch.select_requests(elevator, queue)
+    }
+
+    fn next_stop(
+        &self,
+        elevator: &Elevator,
+        requests: &[ButtonPressed],
+        direction: Direction,
+    ) -> Option<(usize, Direction)> {
+        sweep_next_stop(elevator.elevator_current_floor, requests, direction, None)
+    }
+
+    fn collect_en_route(
+        &self,
+        elevator: &Elevator,
+        requests: &[ButtonPressed],
+        direction: Direction,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Vec<ButtonPressed> {
+        collect_in_direction(elevator, requests, direction, queue)
+    }
+
+    fn should_board(&self, request: &ButtonPressed, direction: Direction) -> bool {
+        request.direction() == direction
+    }
+}
+
+/// SCAN: like LOOK, but always travels to the end of the shaft before
+/// reversing.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanDispatch {
+    pub top_floor: usize,
+}
+
+impl DispatchStrategy for ScanDispatch {
+    fn name(&self) -> &'static str {
+        "scan"
+    }
+
+    fn select_requests(
+        &self,
+        elevator: &Elevator,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Option<VecDeque<ButtonPressed>> {
+        DirectionalBatchDispatch.select_requests(elevator, queue)
+    }
+
+    fn next_stop(
+        &self,
+        elevator: &Elevator,
+        requests: &[ButtonPressed],
+        direction: Direction,
+    ) -> Option<(usize, Direction)> {
+        sweep_next_stop(
+            elevator.elevator_current_floor,
+            requests,
+            direction,
+            Some(self.top_floor),
+        )
+    }
+
+    fn collect_en_route(
+        &self,
+        elevator: &Elevator,
+        requests: &[ButtonPressed],
+        direction: Direction,
+        queue: &mut VecDeque<ButtonPressed>,
+    ) -> Vec<ButtonPressed> {
+        collect_in_direction(elevator, requests, direction, queue)
+    }
+
+    fn should_board(&self, request: &ButtonPressed, direction: Direction) -> bool {
+        request.direction() == direction
+    }
+}
+
+/// Names accepted by `dispatch_strategy`.
+pub const DISPATCH_STRATEGIES: &[&str] = &["batch", "look", "scan"];
+
+/// Look up a dispatch strategy by name, e.g. from a command line flag.
+///
+/// `top_floor` is the highest floor of the building, used by SCAN.
+pub fn dispatch_strategy(name: &str, top_floor: usize) -> Option<Arc<dyn DispatchStrategy>> {
+    match name {
+        "batch" => Some(Arc::new(DirectionalBatchDispatch)),
+        "look" => Some(Arc::new(LookDispatch)),
+        "scan" => Some(Arc::new(ScanDispatch { top_floor })),
+        _ => None,
+    }
+}
+
+pub fn elevator_process_request(
+    elevator: &Mutex<Elevator>,
+    button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
+    elevator_requests_queue: &Mutex<VecDeque<ButtonPressed>>,
+    elevator_request_s: &mpsc::Sender<QueueStatus>,
+    complete_receiving_buttons: &Arc<Mutex<bool>>,
+    complete: &Arc<Mutex<bool>>,
+    in_service: bool,
+) -> Result<(), ElevatorError> {
+    // Skip this tick while the car is busy serving its last batch.
+    let elevator = match elevator.try_lock() {
+        Ok(elevator) => elevator,
+        Err(TryLockError::WouldBlock) => return Ok(()),
+        Err(TryLockError::Poisoned(_)) => return Err(ElevatorError::LockPoisoned("elevator")),
+    };
+    let request_queue = if in_service {
+        elevator.process_requests(lock(button_press_queue, "hall calls")?)
+    } else {
+        None
+    };
+    // Calls made during a power failure wait for it to end
+    let powered = !*lock(&elevator.power_outage, "power outage")?;
+    drop(elevator);
+
+    let send = |queue_status| {
+        elevator_request_s
+            .send(queue_status)
+            .map_err(|_| ElevatorError::ChannelClosed("car requests"))
+    };
+
+    if let Some(request_queue) = request_queue {
+        let mut elevator_requests_queue = lock(elevator_requests_queue, "car requests")?;
+        let request_queue_count = request_queue.len();
+        elevator_requests_queue.extend(request_queue);
+
+        send(QueueStatus::NewQueue(request_queue_count))?;
+    } else if powered && *lock(complete_receiving_buttons, "calls complete")? {
+        let mut complete = lock(complete, "car complete")?;
+        if !*complete {
+            send(QueueStatus::Done)?;
+            *complete = true;
+        } else {
+            send(QueueStatus::Empty)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Serve the next batch sent by `elevator_process_request`. Returns true
+/// once the car is done for the run.
+pub fn elevator_handle_request(
+    elevator: &Mutex<Elevator>,
+    elevator_request_r: &Receiver<QueueStatus>,
+    button_press_queue: &Mutex<VecDeque<ButtonPressed>>,
+    elevator_requests_queue: &Mutex<VecDeque<ButtonPressed>>,
+    elevator_finish_s: &crossbeam_channel::Sender<()>,
+    handle: &JobHandle,
+) -> Result<bool, ElevatorError> {
+    let queue_status = elevator_request_r
+        .recv()
+        .map_err(|_| ElevatorError::ChannelClosed("car requests"))?;
+
+    match queue_status {
+        QueueStatus::NewQueue(request_queue_count) => {
+            let mut elevator = lock(elevator, "elevator")?;
+            // Take the batch off the queue first, so a panic while serving
+            // it does not poison the queue as well
+            let request_queue = lock(elevator_requests_queue, "car requests")?
+                .drain(..request_queue_count)
+                .collect::<VecDeque<_>>();
+            elevator.events.publish(
+                elevator.clock.now(),
+                &SimEvent::RequestsTaken {
+                    elevator_id: elevator.id.clone(),
+                    person_ids: request_queue.iter().map(|r| r.person_id).collect(),
+                },
+            );
+            elevator.handle_requests(&request_queue, request_queue_count, button_press_queue)?;
+        }
+        QueueStatus::Empty => {}
+        QueueStatus::Done => {
+            handle.cancel();
+            elevator_finish_s
+                .send(())
+                .map_err(|_| ElevatorError::ChannelClosed("car finished"))?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// How hall calls reach the cars of a fleet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum DispatchMode {
+    /// Every car takes calls from the shared `button_press_queue`.
+    #[default]
+    Collective,
+    /// Each call is assigned to one car when it is registered, like a
+    /// destination-dispatch lobby kiosk, and only that car serves it.
+    Destination,
+}
+
+/// Names accepted by `dispatch_mode`.
+pub const DISPATCH_MODES: &[&str] = &["collective", "destination"];
+
+/// Look up a dispatch mode by name, e.g. from a command line flag.
+pub fn dispatch_mode(name: &str) -> Option<DispatchMode> {
+    match name {
+        "collective" => Some(DispatchMode::Collective),
+        "destination" => Some(DispatchMode::Destination),
+        _ => None,
+    }
+}
+
+/// Which car a destination-dispatch controller gives a new call to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum CarAllocation {
+    /// Fewest floors between the car and the caller.
+    NearestCar,
+    /// Earliest estimated arrival at the caller's floor.
+    LeastEta,
+    /// Earliest estimated arrival at the caller's destination.
+    #[default]
+    LeastTimeToDestination,
+}
+
+/// Entry point for new calls, shared by the threads feeding a fleet.
+#[derive(Clone)]
+pub struct GroupController {
+    pub mode: DispatchMode,
+    pub allocation: CarAllocation,
+    pub elevators: Vec<Arc<Mutex<Elevator>>>,
+    pub button_press_queue: Arc<Mutex<VecDeque<ButtonPressed>>>,
+    pub car_queues: Vec<Arc<Mutex<VecDeque<ButtonPressed>>>>,
+    /// Cars out of service, and planned maintenance windows.
+    pub maintenance: Arc<Mutex<MaintenanceRegistry>>,
+    /// Whether the building has lost mains power.
+    pub power_outage: Arc<Mutex<bool>>,
+    pub fire_service: Arc<Mutex<FireService>>,
+    pub lamps: Arc<Mutex<CallLamps>>,
+    /// Floors in the building, if known; calls to other floors are rejected.
+    pub floors: Option<usize>,
+    pub clock: Clock,
+    pub events: Arc<dyn EventSink>,
+}
+
+impl GroupController {
+    pub fn new<I, S>(elevator_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::with_configs(
+            elevator_ids
+                .into_iter()
+                .map(|id| (id, ElevatorConfig::default())),
+        )
+    }
+
+    /// A controller for cars built to different configs, e.g. a freight car
+    /// next to faster passenger cars.
+    pub fn with_configs<I, S>(cars: I) -> Self
+    where
+        I: IntoIterator<Item = (S, ElevatorConfig)>,
+        S: Into<String>,
+    {
+        let clock = Clock::wall();
+        let lamps = Arc::new(Mutex::new(CallLamps::new()));
+        let maintenance = Arc::new(Mutex::new(MaintenanceRegistry::new()));
+        let power_outage = Arc::new(Mutex::new(false));
+        let fire_service = Arc::new(Mutex::new(FireService::new()));
+        let elevators = cars
+            .into_iter()
+            .map(|(id, config)| {
+                let mut elevator = Elevator::with_config(id.into(), config);
+                elevator.clock = clock.clone();
+                elevator.lamps = Arc::clone(&lamps);
+                elevator.maintenance = Arc::clone(&maintenance);
+                elevator.power_outage = Arc::clone(&power_outage);
+                elevator.fire_service = Arc::clone(&fire_service);
+                Arc::new(Mutex::new(elevator))
+            })
+            .collect::<Vec<_>>();
+        let car_queues = elevators
+            .iter()
+            .map(|_| Arc::new(Mutex::new(VecDeque::new())))
+            .collect();
+
+        GroupController {
+            mode: DispatchMode::default(),
+            allocation: CarAllocation::default(),
+            elevators,
+            button_press_queue: Arc::new(Mutex::new(VecDeque::new())),
+            car_queues,
+            maintenance,
+            power_outage,
+            fire_service,
+            lamps,
+            floors: None,
+            clock,
+            events: Arc::new(ConsoleSink),
+        }
+    }
+
+    /// Send the events of the controller and its cars to `events`.
+    pub fn set_event_sink(&mut self, events: Arc<dyn EventSink>) -> Result<(), ElevatorError> {
+        for elevator in &self.elevators {
+            lock(elevator, "elevator")?.events = Arc::clone(&events);
+        }
+        self.events = events;
+        Ok(())
+    }
+
+    /// Publish `event`, stamped with the controller's clock.
+    pub fn publish(&self, event: SimEvent) {
+        self.events.publish(self.clock.now(), &event);
+    }
+
+    /// Index of car `elevator_id`, publishing an error if there is none.
+    fn car_index(&self, elevator_id: &str) -> Result<usize, ElevatorError> {
+        for (index, elevator) in self.elevators.iter().enumerate() {
+            // The id is safe to read even if a thread panicked holding the car
+            if elevator.lock().unwrap_or_else(PoisonError::into_inner).id == elevator_id {
+                return Ok(index);
+            }
+        }
+
+        let error = RequestError::UnknownElevator(elevator_id.to_string());
+        self.publish(SimEvent::Error {
+            elevator_id: None,
+            message: error.to_string(),
+        });
+        Err(error.into())
+    }
+
+    /// Take car `elevator_id` out of service; it stops taking new calls, and
+    /// calls already given to it go to the other cars.
+    pub fn start_maintenance(&self, elevator_id: String) -> Result<(), ElevatorError> {
+        let index = self.car_index(&elevator_id)?;
+        if !lock(&self.maintenance, "maintenance")?.start(&elevator_id) {
+            return Ok(());
+        }
+        self.went_out_of_service(index, elevator_id)
+    }
+
+    /// Put car `elevator_id` back in service and share out the calls that
+    /// were waiting for a car.
+    pub fn end_maintenance(&self, elevator_id: String) -> Result<(), ElevatorError> {
+        let index = self.car_index(&elevator_id)?;
+        if !lock(&self.maintenance, "maintenance")?.end(&elevator_id) {
+            return Ok(());
+        }
+        self.came_back_into_service(index, elevator_id)
+    }
+
+    fn went_out_of_service(&self, index: usize, elevator_id: String) -> Result<(), ElevatorError> {
+        self.publish(SimEvent::MaintenanceStarted { elevator_id });
+        self.hand_back(index)
+    }
+
+    /// Give every call car `index` holds to the other cars and evacuate it.
+    fn hand_back(&self, index: usize) -> Result<(), ElevatorError> {
+        let assigned = lock(&self.car_queues[index], "hall calls")?
+            .drain(..)
+            .collect::<Vec<_>>();
+        self.reassign(assigned)?;
+        self.evacuate(index)
+    }
+
+    fn came_back_into_service(
+        &self,
+        index: usize,
+        elevator_id: String,
+    ) -> Result<(), ElevatorError> {
+        lock(&self.elevators[index], "elevator")?.return_to_service()?;
+        self.publish(SimEvent::ReturnedToService {
+            elevator_id: elevator_id.clone(),
+        });
+        // Back during an outage or a recall: go where the other cars went
+        if !self.in_service(&elevator_id)? {
+            self.evacuate(index)?;
+        }
+        self.reassign_waiting()
+    }
+
+    /// Cut the mains power. Every car runs on battery to the nearest floor,
+    /// or its evacuation floor, and lets everyone out; calls keep queueing
+    /// until `restore_power`.
+    pub fn power_outage(&self) -> Result<(), ElevatorError> {
+        {
+            let mut power_outage = lock(&self.power_outage, "power outage")?;
+            if *power_outage {
+                return Ok(());
+            }
+            *power_outage = true;
+        }
+
+        self.publish(SimEvent::PowerOutage);
+        self.for_each_car(|index| self.hand_back(index))
+    }
+
+    /// Bring the mains power back and share out the calls that queued up
+    /// meanwhile.
+    pub fn restore_power(&self) -> Result<(), ElevatorError> {
+        {
+            let mut power_outage = lock(&self.power_outage, "power outage")?;
+            if !*power_outage {
+                return Ok(());
+            }
+            *power_outage = false;
+        }
+
+        let resumed = self.for_each_car(|index| {
+            lock(&self.elevators[index], "elevator")?.restore_power()?;
+            Ok(())
+        });
+        self.publish(SimEvent::PowerRestored);
+
+        // Cars under maintenance, or recalled meanwhile, go where they belong
+        let parked = self.for_each_car(|index| {
+            let elevator_id = lock(&self.elevators[index], "elevator")?.id.clone();
+            if !self.in_service(&elevator_id)? {
+                self.evacuate(index)?;
+            }
+            Ok(())
+        });
+        ElevatorError::collect([resumed, parked, self.reassign_waiting()])
+    }
+
+    /// Run `f` for every car. A car it fails on in a way that calls for
+    /// isolating the car is isolated, and the remaining cars are still dealt
+    /// with; the errors are returned together at the end.
+    fn for_each_car(
+        &self,
+        f: impl Fn(usize) -> Result<(), ElevatorError>,
+    ) -> Result<(), ElevatorError> {
+        let mut results = Vec::new();
+        for index in 0..self.elevators.len() {
+            let Err(e) = f(index) else {
+                continue;
+            };
+            if e.recovery() == Recovery::IsolateCar {
+                results.push(self.isolate(index, e.to_string()));
+            }
+            results.push(Err(e));
+        }
+        ElevatorError::collect(results)
+    }
+
+    /// Fire service Phase I: cancel every call and send every car non-stop
+    /// to `floor`, where it parks with its doors open. Calls are turned
+    /// away until `reset_fire_service`.
+    pub fn fire_recall(&self, floor: usize) -> Result<(), ElevatorError> {
+        if !lock(&self.fire_service, "fire service")?.recall(floor) {
+            return Ok(());
+        }
+        self.publish(SimEvent::FireRecall { floor });
+
+        let mut cancelled = lock(&self.button_press_queue, "hall calls")?
+            .drain(..)
+            .collect::<Vec<_>>();
+        for car_queue in &self.car_queues {
+            cancelled.extend(lock(car_queue, "hall calls")?.drain(..));
+        }
+        for request in cancelled {
+            self.publish(SimEvent::CallCancelled { request });
+        }
+        let (hall_calls, car_calls) = lock(&self.lamps, "call lamps")?.clear();
+        for call in hall_calls {
+            self.publish(SimEvent::HallCallCancelled { call });
+        }
+        for call in car_calls {
+            self.publish(SimEvent::CarCallCancelled { call });
+        }
+
+        self.for_each_car(|index| self.evacuate(index))
+    }
+
+    /// Fire service Phase II: hand car `elevator_id`, parked by the recall,
+    /// to firefighter `person_id`.
+    pub fn firefighter_control(
+        &self,
+        elevator_id: String,
+        person_id: usize,
+    ) -> Result<(), ElevatorError> {
+        let index = self.car_index(&elevator_id)?;
+        let parked = lock(&self.elevators[index], "elevator")?.status == ElevatorState::FireService;
+        if !parked
+            || !lock(&self.fire_service, "fire service")?.take_control(&elevator_id, person_id)
+        {
+            return Err(RequestError::NotInFireService(elevator_id).into());
+        }
+
+        self.publish(SimEvent::FirefighterControl {
+            elevator_id,
+            person_id,
+        });
+        Ok(())
+    }
+
+    /// Car call from `person_id` inside car `elevator_id` during Phase II.
+    /// Returns false if the call was ignored: only the firefighter operating
+    /// the car is answered.
+    pub fn firefighter_call(
+        &self,
+        elevator_id: &str,
+        person_id: usize,
+        floor: usize,
+    ) -> Result<bool, ElevatorError> {
+        let index = self.car_index(elevator_id)?;
+        let Some(operator) = lock(&self.fire_service, "fire service")?.operator(elevator_id) else {
+            return Err(RequestError::NotInFireService(elevator_id.to_string()).into());
+        };
+        if let Some(floors) = self.floors.filter(|floors| floor >= *floors) {
+            return Err(RequestError::FloorOutOfRange { floor, floors }.into());
+        }
+
+        let mut elevator = lock(&self.elevators[index], "elevator")?;
+        if person_id != operator || !elevator.config.serves(floor) {
+            self.publish(SimEvent::CarCallIgnored {
+                elevator_id: elevator_id.to_string(),
+                person_id,
+                floor,
+            });
+            return Ok(false);
+        }
+        elevator.firefighter_trip(floor)?;
+        Ok(true)
+    }
+
+    /// End Phase II for car `elevator_id` and take it back to the recall
+    /// floor.
+    pub fn release_firefighter_control(&self, elevator_id: String) -> Result<(), ElevatorError> {
+        let index = self.car_index(&elevator_id)?;
+        let recall_floor = {
+            let mut fire_service = lock(&self.fire_service, "fire service")?;
+            if !fire_service.release(&elevator_id) {
+                return Ok(());
+            }
+            fire_service.recall_floor()
+        };
+
+        if let Some(floor) = recall_floor {
+            let mut elevator = lock(&self.elevators[index], "elevator")?;
+            let floor = elevator.config.closest_served(floor);
+            if elevator.elevator_current_floor != floor {
+                elevator.firefighter_trip(floor)?;
+            }
+        }
+        self.publish(SimEvent::FirefighterReleased { elevator_id });
+        Ok(())
+    }
+
+    /// End fire service, Phase II included; the cars take calls again.
+    pub fn reset_fire_service(&self) -> Result<(), ElevatorError> {
+        if !lock(&self.fire_service, "fire service")?.reset() {
+            return Ok(());
+        }
+
+        let ended = self.for_each_car(|index| {
+            lock(&self.elevators[index], "elevator")?.end_fire_service()?;
+            Ok(())
+        });
+        self.publish(SimEvent::FireServiceReset);
+        ended
+    }
+
+    /// Carry out a command from the fire panel or a firefighter's key.
+    pub fn fire_command(&self, command: &FireCommand) -> Result<(), ElevatorError> {
+        match command {
+            FireCommand::Recall { floor } => self.fire_recall(*floor),
+            FireCommand::TakeControl {
+                elevator_id,
+                person_id,
+            } => self.firefighter_control(elevator_id.clone(), *person_id),
+            FireCommand::CarCall {
+                elevator_id,
+                person_id,
+                floor,
+            } => self
+                .firefighter_call(elevator_id, *person_id, *floor)
+                .map(|_| ()),
+            FireCommand::Release { elevator_id } => {
+                self.release_firefighter_control(elevator_id.clone())
+            }
+            FireCommand::Reset => self.reset_fire_service(),
+        }
+    }
+
+    /// Stop car `index` at the floor it is at or heading for, let its riders
+    /// out as new calls from there, and give the calls it took but did not
+    /// pick up yet to the other cars.
+    pub fn evacuate(&self, index: usize) -> Result<(), ElevatorError> {
+        let (riders, waiting) = lock(&self.elevators[index], "elevator")?.evacuate()?;
+        self.resubmit(riders)?;
+        self.reassign(waiting)
+    }
+
+    /// Register the calls of people let out of a car short of their floor.
+    fn resubmit(&self, riders: Vec<ButtonPressed>) -> Result<(), ElevatorError> {
+        for rider in riders {
+            // Rejected calls are published by `submit`
+            if let Err(e) = self.submit(rider) {
+                if e.recovery() != Recovery::Retry {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Make `fault` happen now. Lost messages are dropped by whoever sends
+    /// them; the controller only reports the fault.
+    pub fn inject_fault(&self, fault: &Fault) -> Result<(), ElevatorError> {
+        let index = match fault.elevator_id() {
+            Some(elevator_id) => Some(self.car_index(elevator_id)?),
+            None => None,
+        };
+        self.publish(SimEvent::FaultInjected {
+            fault: fault.clone(),
+        });
+
+        match (fault, index) {
+            (Fault::Maintenance { elevator_id, .. }, _) => {
+                self.start_maintenance(elevator_id.clone())
+            }
+            (Fault::PowerOutage { .. }, _) => self.power_outage(),
+            (Fault::Stuck { elevator_id, .. }, Some(index)) => {
+                {
+                    let mut elevator = lock(&self.elevators[index], "elevator")?;
+                    if !lock(&self.maintenance, "maintenance")?.start(elevator_id) {
+                        return Ok(());
+                    }
+                    elevator.stick()?;
+                }
+                self.hand_back(index)
+            }
+            (
+                Fault::DoorJam {
+                    floor, duration_ms, ..
+                },
+                Some(index),
+            ) => {
+                lock(&self.elevators[index], "elevator")?.faults.door_jam = Some(DoorJam {
+                    floor: *floor,
+                    duration: Duration::from_millis(*duration_ms),
+                });
+                Ok(())
+            }
+            (Fault::WrongFloor { reported_floor, .. }, Some(index)) => {
+                lock(&self.elevators[index], "elevator")?
+                    .faults
+                    .misreported_floor = Some(*reported_floor);
+                Ok(())
+            }
+            (Fault::WorkerPanic { .. }, Some(index)) => {
+                lock(&self.elevators[index], "elevator")?
+                    .faults
+                    .worker_panic = true;
+                Ok(())
+            }
+            (Fault::PoisonLock { .. }, Some(index)) => {
+                let elevator = Arc::clone(&self.elevators[index]);
+                // The car's own threads find the lock poisoned and isolate it
+                let _ = thread::spawn(move || {
+                    let _elevator = elevator.lock();
+                    panic!("injected fault while holding the elevator lock");
+                })
+                .join();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Undo `fault` once it has run its course: end the maintenance or the
+    /// outage, or rescue the stuck car and put it back in service.
+    pub fn clear_fault(&self, fault: &Fault) -> Result<(), ElevatorError> {
+        self.publish(SimEvent::FaultCleared {
+            fault: fault.clone(),
+        });
+
+        match fault {
+            Fault::Maintenance { elevator_id, .. } => self.end_maintenance(elevator_id.clone()),
+            Fault::PowerOutage { .. } => self.restore_power(),
+            Fault::Stuck { elevator_id, .. } => {
+                let index = self.car_index(elevator_id)?;
+                let riders = {
+                    let mut elevator = lock(&self.elevators[index], "elevator")?;
+                    // Out for some other reason when the fault was injected
+                    if !elevator.faults.stuck {
+                        return Ok(());
+                    }
+                    elevator.rescue()?
+                };
+                self.resubmit(riders)?;
+                self.end_maintenance(elevator_id.clone())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Take car `index` out of service for the rest of the run after its
+    /// worker panicked or its lock was poisoned, and give its calls to the
+    /// other cars.
+    pub fn isolate(&self, index: usize, reason: String) -> Result<(), ElevatorError> {
+        let elevator = &self.elevators[index];
+        // Whatever the panicking thread left half done, the car is emptied
+        // and parked below, so its state is usable again
+        let elevator_id = elevator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .id
+            .clone();
+        elevator.clear_poison();
+        if !lock(&self.maintenance, "maintenance")?.start(&elevator_id) {
+            return Ok(());
+        }
+
+        self.publish(SimEvent::CarIsolated {
+            elevator_id,
+            reason,
+        });
+        self.hand_back(index)
+    }
+
+    /// Isolate whichever cars `error` is about: the car named in a failed
+    /// transition, and every car whose lock a panicking thread poisoned.
+    pub fn isolate_failed(&self, error: &ElevatorError) -> Result<(), ElevatorError> {
+        match error {
+            ElevatorError::Transition(e) => {
+                self.isolate(self.car_index(&e.elevator_id)?, error.to_string())
+            }
+            ElevatorError::LockPoisoned(_) | ElevatorError::WorkerPanicked(_) => {
+                ElevatorError::collect(
+                    (0..self.elevators.len())
+                        .filter(|index| self.elevators[*index].is_poisoned())
+                        .map(|index| self.isolate(index, error.to_string())),
+                )
+            }
+            ElevatorError::Fault(fault) => match fault.elevator_id() {
+                Some(elevator_id) => self.contain_fault(self.car_index(elevator_id)?, fault),
+                None => Ok(()),
+            },
+            ElevatorError::Several(errors) => {
+                ElevatorError::collect(errors.iter().map(|e| self.isolate_failed(e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Take car `index` out after `error` hit it: only for as long as a fault
+    /// that played out in it lasts, otherwise for the rest of the run.
+    pub fn isolate_car(&self, index: usize, error: &ElevatorError) -> Result<(), ElevatorError> {
+        match error {
+            ElevatorError::Fault(fault) => self.contain_fault(index, fault),
+            _ => self.isolate(index, error.to_string()),
+        }
+    }
+
+    /// Isolate car `index` after `fault` played out in it and give its riders
+    /// and calls to the other cars. A car with jammed doors comes back once
+    /// they are freed, and a car that misread its floor once it has run to
+    /// the terminal floor to find it again.
+    fn contain_fault(&self, index: usize, fault: &Fault) -> Result<(), ElevatorError> {
+        let (elevator_id, freed_at) = {
+            let elevator = lock(&self.elevators[index], "elevator")?;
+            (elevator.id.clone(), elevator.busy_until)
+        };
+        if lock(&self.maintenance, "maintenance")?.contains(&elevator_id) {
+            return Ok(());
+        }
+
+        match fault {
+            Fault::DoorJam { .. } => {
+                self.publish(SimEvent::CarIsolated {
+                    elevator_id: elevator_id.clone(),
+                    reason: fault.to_string(),
+                });
+                let now = self.clock.now();
+                self.schedule_maintenance(MaintenanceWindow {
+                    elevator_id,
+                    start_ms: now.as_millis() as u64,
+                    end_ms: freed_at.max(now).as_nanos().div_ceil(1_000_000) as u64,
+                })
+            }
+            Fault::WrongFloor { .. } => {
+                self.isolate(index, fault.to_string())?;
+                self.end_maintenance(elevator_id)
+            }
+            _ => self.isolate(index, fault.to_string()),
+        }
+    }
+
+    /// Plan a maintenance window; it opens and closes as the clock passes it.
+    pub fn schedule_maintenance(&self, window: MaintenanceWindow) -> Result<(), ElevatorError> {
+        self.car_index(&window.elevator_id)?;
+        lock(&self.maintenance, "maintenance")?.schedule(window);
+        self.apply_maintenance_schedule()
+    }
+
+    /// Open and close every maintenance window due by now.
+    pub fn apply_maintenance_schedule(&self) -> Result<(), ElevatorError> {
+        let changes = lock(&self.maintenance, "maintenance")?.due(self.clock.now());
+        for elevator_id in changes.started {
+            self.went_out_of_service(self.car_index(&elevator_id)?, elevator_id)?;
+        }
+        for elevator_id in changes.ended {
+            self.came_back_into_service(self.car_index(&elevator_id)?, elevator_id)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn in_service(&self, elevator_id: &str) -> Result<bool, ElevatorError> {
+        Ok(
+            !lock(&self.maintenance, "maintenance")?.contains(elevator_id)
+                && !*lock(&self.power_outage, "power outage")?
+                && !lock(&self.fire_service, "fire service")?.is_active(),
+        )
+    }
+
+    /// Give `requests` to the cars still in service. In destination mode a
+    /// call no car can take now waits on the shared queue for one to return.
+    pub fn reassign(&self, requests: Vec<ButtonPressed>) -> Result<(), ElevatorError> {
+        for mut request in requests {
+            let index = match self.mode {
+                DispatchMode::Collective => None,
+                DispatchMode::Destination => self.allocate(&request)?,
+            };
+            let Some(index) = index else {
+                lock(&self.button_press_queue, "hall calls")?.push_back(request);
+                continue;
+            };
+
+            request.assigned_at = Some(self.clock.now());
+            lock(&self.car_queues[index], "hall calls")?.push_back(request);
+            let elevator_id = lock(&self.elevators[index], "elevator")?.id.clone();
+            self.publish(SimEvent::CarAssigned {
+                elevator_id,
+                person_id: request.person_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// In destination mode, try again to allocate calls that found no car.
+    fn reassign_waiting(&self) -> Result<(), ElevatorError> {
+        if self.mode == DispatchMode::Collective {
+            return Ok(());
+        }
+        let waiting = lock(&self.button_press_queue, "hall calls")?
+            .drain(..)
+            .collect::<Vec<_>>();
+        self.reassign(waiting)
+    }
+
+    /// Switch every car to `strategy`, taking effect from its next batch.
+    pub fn set_strategy(&self, strategy: Arc<dyn DispatchStrategy>) -> Result<(), ElevatorError> {
+        for elevator in &self.elevators {
+            lock(elevator, "elevator")?.strategy = Arc::clone(&strategy);
+        }
+        Ok(())
+    }
+
+    /// Use `clock` for every timestamp taken by the controller and its cars.
+    pub fn set_clock(&mut self, clock: Clock) -> Result<(), ElevatorError> {
+        for elevator in &self.elevators {
+            lock(elevator, "elevator")?.clock = clock.clone();
+        }
+        self.clock = clock;
+        Ok(())
+    }
+
+    /// Passenger KPIs of every journey finished so far.
+    ///
+    /// Only meaningful on a virtual clock: on the wall clock calls are stamped
+    /// in real time, while cars move in cost-model time without waiting.
+    pub fn report(&self) -> Result<KpiReport, ElevatorError> {
+        KpiReport::new(&self.elevators)
+    }
+
+    /// Calls waiting for car `index`: its own queue in destination mode,
+    /// the shared queue otherwise.
+    pub fn hall_calls(&self, index: usize) -> &Arc<Mutex<VecDeque<ButtonPressed>>> {
+        match self.mode {
+            DispatchMode::Collective => &self.button_press_queue,
+            DispatchMode::Destination => &self.car_queues[index],
+        }
+    }
+
+    /// Register a call. In destination mode the call is given to one car,
+    /// chosen by `allocation`, and that car's id is returned; otherwise it is
+    /// left on the shared queue for any car.
+    ///
+    /// Zero-length trips and floors outside the building are rejected, and
+    /// so is every call while the cars are recalled for fire service.
+    pub fn submit(&self, request: ButtonPressed) -> Result<Option<String>, ElevatorError> {
+        let recalled = lock(&self.fire_service, "fire service")?.is_active();
+        let validated = request.validate(self.floors).and(if recalled {
+            Err(RequestError::FireRecall)
+        } else {
+            Ok(())
+        });
+        if let Err(error) = validated {
+            self.publish(SimEvent::CallRejected {
+                request,
+                reason: error.to_string(),
+            });
+            return Err(error.into());
+        }
+
+        let now = self.clock.now();
+        let mut request = self.plan_leg(request)?;
+        request.registered_at.get_or_insert(now);
+        self.publish(SimEvent::CallRegistered { request });
+        let call = request.hall_call();
+        if lock(&self.lamps, "call lamps")?.press_hall(call, request.person_id) {
+            self.publish(SimEvent::HallCallLit { call });
+        }
+        if request.destination.is_some() {
+            self.publish(SimEvent::TransferPlanned {
+                person_id: request.person_id,
+                floor: request.target_floor,
+            });
+        }
+
+        if self.mode == DispatchMode::Collective {
+            lock(&self.button_press_queue, "hall calls")?.push_back(request);
+            return Ok(None);
+        }
+
+        let Some(index) = self.allocate(&request)? else {
+            // Every car that could take it is out of service: wait for one
+            lock(&self.button_press_queue, "hall calls")?.push_back(request);
+            return Ok(None);
+        };
+        request.assigned_at.get_or_insert(now);
+        lock(&self.car_queues[index], "hall calls")?.push_back(request);
+        let elevator_id = lock(&self.elevators[index], "elevator")?.id.clone();
+        self.publish(SimEvent::CarAssigned {
+            elevator_id: elevator_id.clone(),
+            person_id: request.person_id,
+        });
+        Ok(Some(elevator_id))
+    }
+
+    /// Cut `request` short at a transfer floor when no car serves both its
+    /// floors, e.g. between a low-rise and a high-rise bank. The remaining
+    /// legs are planned again once the person gets off there.
+    pub fn plan_leg(&self, request: ButtonPressed) -> Result<ButtonPressed, ElevatorError> {
+        let maintenance = lock(&self.maintenance, "maintenance")?.clone();
+        let mut configs = Vec::new();
+        for elevator in &self.elevators {
+            // A car with a poisoned lock is about to be isolated
+            let Ok(elevator) = elevator.lock() else {
+                continue;
+            };
+            if !maintenance.contains(&elevator.id) {
+                configs.push(elevator.config.clone());
+            }
+        }
+        let one_car = |from: usize, to: usize| {
+            configs
+                .iter()
+                .any(|config| config.serves(from) && config.serves(to))
+        };
+
+        let (origin, destination) = (request.current_floor, request.target_floor);
+        if one_car(origin, destination) {
+            return Ok(request);
+        }
+
+        // Fewest legs: breadth-first search over the floors cars stop at
+        let mut floors = configs
+            .iter()
+            .flat_map(|config| config.served_floors.iter().copied())
+            .collect::<Vec<_>>();
+        floors.sort_unstable();
+        floors.dedup();
+
+        let mut reached_from = vec![(origin, origin)];
+        let mut frontier = VecDeque::from([origin]);
+        while let Some(floor) = frontier.pop_front() {
+            if one_car(floor, destination) {
+                // Walk back to the first hop after the origin
+                let mut hop = floor;
+                while let Some(&(_, from)) = reached_from.iter().find(|(to, _)| *to == hop) {
+                    if from == origin {
+                        break;
+                    }
+                    hop = from;
+                }
+
+                return Ok(ButtonPressed {
+                    target_floor: hop,
+                    destination: Some(request.destination.unwrap_or(destination)),
+                    ..request
+                });
+            }
+            for &next in &floors {
+                if one_car(floor, next) && !reached_from.iter().any(|(to, _)| *to == next) {
+                    reached_from.push((next, floor));
+                    frontier.push_back(next);
+                }
+            }
+        }
+
+        // No way there at all: leave it for whoever can take it
+        Ok(request)
+    }
+
+    /// Index of the car best placed to serve `request`, skipping any car
+    /// under maintenance, with a poisoned lock or with no estimate for it.
+    pub fn allocate(&self, request: &ButtonPressed) -> Result<Option<usize>, ElevatorError> {
+        let mut best = None;
+        for (i, elevator) in self.elevators.iter().enumerate() {
+            let Ok(elevator) = elevator.lock() else {
+                continue;
+            };
+            if !elevator.in_service()? || !elevator.config.serves_trip(request) {
+                continue;
+            }
+            let mut assigned = lock(&self.car_queues[i], "hall calls")?;
+            let Some(eta) = elevator.estimated_time_of_arrival(assigned.make_contiguous(), request)
+            else {
+                continue;
+            };
+            let cost = match self.allocation {
+                CarAllocation::NearestCar => {
+                    let distance = elevator
+                        .elevator_current_floor
+                        .abs_diff(request.current_floor);
+                    let travel_time = elevator.cost_model.travel_time(distance);
+                    (travel_time, eta.pick_up)
+                }
+                CarAllocation::LeastEta => (eta.pick_up, eta.destination),
+                CarAllocation::LeastTimeToDestination => (eta.destination, eta.pick_up),
+            };
+            if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                best = Some((cost, i));
+            }
+        }
+
+        Ok(best.map(|(_, i)| i))
+    }
+}
+
+/// A group of elevators serving one shared `button_press_queue`.
+///
+/// Every car gets a periodic `elevator_process_request` job on the scheduled
+/// pool and an aperiodic `elevator_handle_request` worker on the thread pool,
+/// so any number of cars can be run without copying the setup per car.
+pub struct Fleet {
+    pub mode: DispatchMode,
+    pub allocation: CarAllocation,
+    pub elevators: Vec<Arc<Mutex<Elevator>>>,
+    pub button_press_queue: Arc<Mutex<VecDeque<ButtonPressed>>>,
+    pub car_queues: Vec<Arc<Mutex<VecDeque<ButtonPressed>>>>,
+    pub complete_receiving_buttons: Arc<Mutex<bool>>,
+    pub maintenance: Arc<Mutex<MaintenanceRegistry>>,
+    pub power_outage: Arc<Mutex<bool>>,
+    pub fire_service: Arc<Mutex<FireService>>,
+    pub lamps: Arc<Mutex<CallLamps>>,
+    pub floors: Option<usize>,
+    pub clock: Clock,
+    pub events: Arc<dyn EventSink>,
+    scheduled_thread_pool: ScheduledThreadPool,
+    pool: ThreadPool,
+    finish_receivers: Vec<crossbeam_channel::Receiver<()>>,
+}
+
+impl Fleet {
+    pub fn new<I, S>(elevator_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::with_configs(
+            elevator_ids
+                .into_iter()
+                .map(|id| (id, ElevatorConfig::default())),
+        )
+    }
+
+    pub fn with_configs<I, S>(cars: I) -> Self
+    where
+        I: IntoIterator<Item = (S, ElevatorConfig)>,
+        S: Into<String>,
+    {
+        let GroupController {
+            mode,
+            allocation,
+            elevators,
+            button_press_queue,
+            car_queues,
+            maintenance,
+            power_outage,
+            fire_service,
+            lamps,
+            floors,
+            clock,
+            events,
+        } = GroupController::with_configs(cars);
+        // One blocking worker per car, so the pool must be at least that big.
+        let pool = ThreadPool::new(elevators.len().max(1));
+
+        Fleet {
+            mode,
+            allocation,
+            elevators,
+            button_press_queue,
+            car_queues,
+            complete_receiving_buttons: Arc::new(Mutex::new(false)),
+            maintenance,
+            power_outage,
+            fire_service,
+            lamps,
+            floors,
+            clock,
+            events,
+            scheduled_thread_pool: ScheduledThreadPool::new(2),
+            pool,
+            finish_receivers: Vec::new(),
+        }
+    }
+
+    /// Spawn the process (periodic) and handle (aperiodic) tasks of every car.
+    pub fn start(&mut self, initial_delay: Duration, rate: Duration) -> Result<(), ElevatorError> {
+        let controller = self.controller();
+        for (index, elevator) in self.elevators.iter().enumerate() {
+            let elevator_id = lock(elevator, "elevator")?.id.clone();
+            // Cars only see the calls assigned to them in destination mode
+            let hall_calls = Arc::clone(controller.hall_calls(index));
+            let (elevator_request_s, elevator_request_r) = mpsc::channel();
+            let (elevator_finish_s, elevator_finish_r) = crossbeam_channel::unbounded();
+            self.finish_receivers.push(elevator_finish_r);
+
+            let elevator_requests_queue = Arc::new(Mutex::new(VecDeque::new()));
+
+            // Process request: get all the people that need to fetch (Periodic Task)
+            let handle = {
+                let elevator_id = elevator_id.clone();
+                let elevator = Arc::clone(elevator);
+                let elevator_requests_queue = Arc::clone(&elevator_requests_queue);
+                let hall_calls = Arc::clone(&hall_calls);
+                let complete_receiving_buttons = Arc::clone(&self.complete_receiving_buttons);
+                let complete = Arc::new(Mutex::new(false));
+                let controller = controller.clone();
+                let mut failed = false;
+
+                self.scheduled_thread_pool
+                    .execute_at_fixed_rate(initial_delay, rate, move || {
+                        // The worker deals with a failed car; report it only once
+                        if failed {
+                            return;
+                        }
+
+                        // A car out of service takes no calls, but still finishes
+                        // with the others at the end of the run
+                        let ticked = controller
+                            .apply_maintenance_schedule()
+                            .and_then(|()| controller.in_service(&elevator_id))
+                            .and_then(|in_service| {
+                                elevator_process_request(
+                                    &elevator,
+                                    &hall_calls,
+                                    &elevator_requests_queue,
+                                    &elevator_request_s,
+                                    &complete_receiving_buttons,
+                                    &complete,
+                                    in_service,
+                                )
+                            });
+
+                        if let Err(e) = ticked {
+                            controller.publish(SimEvent::Error {
+                                elevator_id: Some(elevator_id.clone()),
+                                message: e.to_string(),
+                            });
+                            // An isolated car keeps ticking so it still finishes
+                            failed = match e.recovery() {
+                                Recovery::Retry => false,
+                                Recovery::IsolateCar => controller.isolate_car(index, &e).is_err(),
+                                Recovery::Shutdown => true,
+                            };
+                        }
+                    })
+            };
+
+            // Handle request: only execute when it receive the message (Aperiodic Task)
+            let elevator = Arc::clone(elevator);
+            let controller = controller.clone();
+            let complete_receiving_buttons = Arc::clone(&self.complete_receiving_buttons);
+            self.pool.execute(move || {
+                loop {
+                    let handled = panic::catch_unwind(AssertUnwindSafe(|| {
+                        elevator_handle_request(
+                            &elevator,
+                            &elevator_request_r,
+                            &hall_calls,
+                            &elevator_requests_queue,
+                            &elevator_finish_s,
+                            &handle,
+                        )
+                    }))
+                    .unwrap_or_else(|panic| Err(ElevatorError::worker_panicked(panic)))
+                    .and_then(|done| {
+                        // Register the next leg of anyone who got off to transfer
+                        let transfers = std::mem::take(&mut lock(&elevator, "elevator")?.transfers);
+                        for next_leg in transfers {
+                            // Rejected legs are published by `submit`
+                            if let Err(e) = controller.submit(next_leg) {
+                                if e.recovery() != Recovery::Retry {
+                                    return Err(e);
+                                }
+                            }
+                        }
+
+                        // Hand back what a car taken out mid-trip did not serve
+                        if !controller.in_service(&elevator_id)? {
+                            controller.evacuate(index)?;
+                        }
+                        Ok(done)
+                    });
+
+                    match handled {
+                        // QueueStatus is done.
+                        Ok(true) => break,
+                        Ok(false) => {}
+                        Err(e) => {
+                            let recovery = e.recovery();
+                            controller.publish(SimEvent::Error {
+                                elevator_id: Some(elevator_id.clone()),
+                                message: e.to_string(),
+                            });
+                            if recovery == Recovery::Retry {
+                                continue;
+                            }
+                            // Keep serving the channel out of service, so the
+                            // car still finishes with the others
+                            if recovery == Recovery::IsolateCar
+                                && controller.isolate_car(index, &e).is_ok()
+                            {
+                                continue;
+                            }
+                            if recovery == Recovery::Shutdown {
+                                if let Ok(mut complete) = complete_receiving_buttons.lock() {
+                                    *complete = true;
+                                }
+                            }
+
+                            // Take the car out: no more ticks, and let `wait` finish
+                            handle.cancel();
+                            let _ = elevator_finish_s.send(());
+                            break;
+                        }
+                    }
+                }
+                controller.publish(SimEvent::CarStopped { elevator_id });
+            });
+        }
+        Ok(())
+    }
+
+    /// Handle for registering calls from other threads.
+    pub fn controller(&self) -> GroupController {
+        GroupController {
+            mode: self.mode,
+            allocation: self.allocation,
+            elevators: self.elevators.clone(),
+            button_press_queue: Arc::clone(&self.button_press_queue),
+            car_queues: self.car_queues.clone(),
+            maintenance: Arc::clone(&self.maintenance),
+            power_outage: Arc::clone(&self.power_outage),
+            fire_service: Arc::clone(&self.fire_service),
+            lamps: Arc::clone(&self.lamps),
+            floors: self.floors,
+            clock: self.clock.clone(),
+            events: Arc::clone(&self.events),
+        }
+    }
+
+    /// Send the events of the fleet and its cars to `events`.
+    pub fn set_event_sink(&mut self, events: Arc<dyn EventSink>) -> Result<(), ElevatorError> {
+        for elevator in &self.elevators {
+            lock(elevator, "elevator")?.events = Arc::clone(&events);
+        }
+        self.events = events;
+        Ok(())
+    }
+
+    /// Switch every car to `strategy`, taking effect from its next tick.
+    pub fn set_strategy(&self, strategy: Arc<dyn DispatchStrategy>) -> Result<(), ElevatorError> {
+        for elevator in &self.elevators {
+            lock(elevator, "elevator")?.strategy = Arc::clone(&strategy);
+        }
+        Ok(())
+    }
+
+    /// Current state of every car, in fleet order.
+    pub fn snapshots(&self) -> Result<Vec<ElevatorSnapshot>, ElevatorError> {
+        self.elevators
+            .iter()
+            .map(|elevator| lock(elevator, "elevator")?.snapshot())
+            .collect()
+    }
+
+    /// True once every car has reported that it is done.
+    pub fn is_finished(&self) -> bool {
+        self.finish_receivers.iter().all(|r| !r.is_empty())
+    }
+
+    /// Block until all button presses were received and every car is done.
+    pub fn wait(&self) {
+        loop {
+            // A poisoned flag means intake died; wait for the cars regardless
+            if !self
+                .complete_receiving_buttons
+                .lock()
+                .map_or(true, |complete| *complete)
+            {
+                continue;
+            } else if self.is_finished() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventRecorder;
+
+    /// A controller on a virtual clock, recording everything it publishes.
+    fn controller(elevator_ids: &[&str]) -> (GroupController, Arc<EventRecorder>) {
+        let mut controller = GroupController::new(elevator_ids.iter().copied());
+        controller.set_clock(Clock::virtual_time()).unwrap();
+        let events = Arc::new(EventRecorder::new());
+        controller.set_event_sink(events.clone()).unwrap();
+        (controller, events)
+    }
+
+    /// Poison `elevator`'s lock the way a panicking thread would.
+    fn poison(elevator: &Arc<Mutex<Elevator>>) {
+        let elevator = Arc::clone(elevator);
+        let _ = thread::spawn(move || {
+            let _elevator = elevator.lock();
+            panic!("poisoned on purpose");
+        })
+        .join();
+    }
+
+    fn status(controller: &GroupController, index: usize) -> ElevatorState {
+        lock(&controller.elevators[index], "elevator")
+            .unwrap()
+            .status
+    }
+
+    #[test]
+    fn power_outage_deals_with_every_car_when_one_is_poisoned() {
+        let (controller, events) = controller(&["A", "B", "C"]);
+        poison(&controller.elevators[1]);
+
+        let outage = controller.power_outage();
+        assert!(matches!(
+            outage,
+            Err(ElevatorError::LockPoisoned("elevator"))
+        ));
+        for index in 0..3 {
+            assert_eq!(status(&controller, index), ElevatorState::EmergencyStop);
+        }
+        assert!(events.events().unwrap().iter().any(|(_, event)| matches!(
+            event,
+            SimEvent::CarIsolated { elevator_id, .. } if elevator_id == "B"
+        )));
+
+        controller.restore_power().unwrap();
+        assert_eq!(status(&controller, 0), ElevatorState::Idle);
+        assert_eq!(status(&controller, 1), ElevatorState::Maintenance);
+        assert_eq!(status(&controller, 2), ElevatorState::Idle);
     }
 
-    false
+    #[test]
+    fn isolate_failed_takes_out_only_the_poisoned_car() {
+        let (controller, _) = controller(&["A", "B"]);
+        poison(&controller.elevators[0]);
+
+        let error = ElevatorError::LockPoisoned("elevator");
+        controller.isolate_failed(&error).unwrap();
+        assert!(!controller.elevators[0].is_poisoned());
+        assert!(!controller.in_service("A").unwrap());
+        assert!(controller.in_service("B").unwrap());
+    }
+
+    /// Someone inside car `index`, headed for `floor`.
+    fn rider(controller: &GroupController, index: usize, person_id: usize, floor: usize) {
+        let mut elevator = lock(&controller.elevators[index], "elevator").unwrap();
+        let mut rider =
+            ButtonPressed::new_request(person_id, elevator.elevator_current_floor, floor);
+        rider.entered = true;
+        elevator.requests.push(rider);
+    }
+
+    #[test]
+    fn fire_recall_parks_cars_but_leaves_stuck_riders_for_rescue() {
+        let (controller, _) = controller(&["A", "B", "C"]);
+        lock(&controller.elevators[0], "elevator")
+            .unwrap()
+            .elevator_current_floor = 3;
+        rider(&controller, 0, 1, 5);
+        rider(&controller, 2, 2, 5);
+        lock(&controller.elevators[2], "elevator")
+            .unwrap()
+            .stick()
+            .unwrap();
+
+        controller.fire_recall(0).unwrap();
+        for index in 0..2 {
+            let elevator = lock(&controller.elevators[index], "elevator").unwrap();
+            assert_eq!(elevator.status, ElevatorState::FireService);
+            assert_eq!(elevator.elevator_current_floor, 0);
+            assert!(elevator.requests.is_empty());
+        }
+        let stuck = lock(&controller.elevators[2], "elevator").unwrap();
+        assert_eq!(stuck.status, ElevatorState::EmergencyStop);
+        assert_eq!(
+            stuck
+                .requests
+                .iter()
+                .map(|r| r.person_id)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+        drop(stuck);
+
+        let call = controller.submit(ButtonPressed::new_request(3, 1, 4));
+        assert!(matches!(
+            call,
+            Err(ElevatorError::Request(RequestError::FireRecall))
+        ));
+    }
+
+    #[test]
+    fn phase_ii_answers_only_the_firefighter_holding_the_key() {
+        let (controller, _) = controller(&["A", "B"]);
+        let take_control = FireCommand::TakeControl {
+            elevator_id: "A".to_string(),
+            person_id: 9,
+        };
+        assert!(controller.fire_command(&take_control).is_err());
+
+        controller.fire_recall(0).unwrap();
+        controller.fire_command(&take_control).unwrap();
+        assert!(!controller.firefighter_call("A", 4, 6).unwrap());
+        assert!(controller.firefighter_call("A", 9, 6).unwrap());
+        {
+            let elevator = lock(&controller.elevators[0], "elevator").unwrap();
+            assert_eq!(elevator.elevator_current_floor, 6);
+            assert_eq!(elevator.status, ElevatorState::FireService);
+        }
+        // Only the car whose key was turned runs
+        assert!(controller.firefighter_call("B", 9, 6).is_err());
+
+        controller
+            .release_firefighter_control("A".to_string())
+            .unwrap();
+        assert_eq!(
+            lock(&controller.elevators[0], "elevator")
+                .unwrap()
+                .elevator_current_floor,
+            0
+        );
+    }
+
+    #[test]
+    fn fire_service_reset_puts_every_car_back_in_service() {
+        let (controller, events) = controller(&["A", "B"]);
+        controller.fire_recall(2).unwrap();
+        controller.fire_command(&FireCommand::Reset).unwrap();
+
+        for index in 0..2 {
+            assert_eq!(status(&controller, index), ElevatorState::Idle);
+        }
+        assert!(controller.in_service("A").unwrap());
+        assert!(events
+            .events()
+            .unwrap()
+            .iter()
+            .any(|(_, event)| *event == SimEvent::FireServiceReset));
+        controller
+            .submit(ButtonPressed::new_request(1, 0, 4))
+            .unwrap();
+    }
+
+    #[test]
+    fn idle_car_lets_its_riders_out_before_going_out_of_service() {
+        let (controller, _) = controller(&["A", "B"]);
+        lock(&controller.elevators[0], "elevator")
+            .unwrap()
+            .elevator_current_floor = 2;
+        rider(&controller, 0, 1, 5);
+        assert_eq!(status(&controller, 0), ElevatorState::Idle);
+
+        controller.start_maintenance("A".to_string()).unwrap();
+        assert_eq!(status(&controller, 0), ElevatorState::Maintenance);
+        assert!(lock(&controller.elevators[0], "elevator")
+            .unwrap()
+            .requests
+            .is_empty());
+        let waiting = lock(&controller.button_press_queue, "hall calls")
+            .unwrap()
+            .iter()
+            .map(|call| (call.person_id, call.current_floor, call.target_floor))
+            .collect::<Vec<_>>();
+        assert_eq!(waiting, [(1, 2, 5)]);
+    }
+
+    #[test]
+    fn evacuated_riders_keep_their_call_time_and_arrive_if_already_there() {
+        let (controller, _) = controller(&["A", "B"]);
+        controller.clock.set(Duration::from_secs(3));
+        {
+            let mut elevator = lock(&controller.elevators[0], "elevator").unwrap();
+            elevator.elevator_current_floor = 2;
+            elevator.transition(ElevatorState::DoorsOpening).unwrap();
+            elevator.transition(ElevatorState::DoorsOpen).unwrap();
+        }
+        rider(&controller, 0, 1, 2);
+        rider(&controller, 0, 2, 5);
+        for rider in &mut lock(&controller.elevators[0], "elevator").unwrap().requests {
+            rider.registered_at = Some(Duration::from_secs(1));
+        }
+
+        controller.start_maintenance("A".to_string()).unwrap();
+        let completed = lock(&controller.elevators[0], "elevator")
+            .unwrap()
+            .completed
+            .clone();
+        assert_eq!(
+            completed.iter().map(|r| r.person_id).collect::<Vec<_>>(),
+            [1]
+        );
+        let waiting = lock(&controller.button_press_queue, "hall calls")
+            .unwrap()
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].person_id, 2);
+        assert_eq!(waiting[0].current_floor, 2);
+        assert_eq!(waiting[0].registered_at, Some(Duration::from_secs(1)));
+        assert_eq!(waiting[0].transferred_at, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn eta_counts_the_work_a_car_already_committed_to() {
+        let (controller, _) = controller(&["A"]);
+        let request = ButtonPressed::new_request(1, 0, 3);
+        let idle = lock(&controller.elevators[0], "elevator")
+            .unwrap()
+            .estimated_time_of_arrival(&[], &request)
+            .unwrap();
+
+        {
+            let mut elevator = lock(&controller.elevators[0], "elevator").unwrap();
+            elevator.transition(ElevatorState::MovingUp).unwrap();
+            let trip = elevator.depart(6);
+            elevator.busy_until = trip.departed_at + trip.travel_time;
+        }
+        let elevator = lock(&controller.elevators[0], "elevator").unwrap();
+        let busy = elevator.estimated_time_of_arrival(&[], &request).unwrap();
+        let back_down = elevator.cost_model.travel_time(6);
+        assert!(busy.pick_up >= idle.pick_up + elevator.busy_until + back_down);
+    }
+
+    #[test]
+    fn configured_strategy_reaches_every_car() {
+        let (controller, _) = controller(&["A", "B"]);
+        let config = SimulationConfig {
+            seed: 0,
+            events_path: None,
+            strategy: Some("scan".to_string()),
+            dispatch: None,
+        };
+        for name in DISPATCH_STRATEGIES {
+            assert!(dispatch_strategy(name, 9).is_some());
+        }
+
+        controller
+            .set_strategy(config.dispatch_strategy(9).unwrap())
+            .unwrap();
+        for elevator in &controller.elevators {
+            assert_eq!(lock(elevator, "elevator").unwrap().strategy.name(), "scan");
+        }
+        let unknown = SimulationConfig {
+            strategy: Some("elevator music".to_string()),
+            ..config
+        };
+        assert!(unknown.dispatch_strategy(9).is_none());
+    }
+
+    #[test]
+    fn configure_sets_up_every_car_for_the_scenario() {
+        let (mut controller, _) = controller(&["A", "B"]);
+        let scenario =
+            Scenario::from_csv("floors,10\nelevators,A,B\nmaintenance,B,0,10000\n").unwrap();
+        let config = SimulationConfig {
+            seed: 0,
+            events_path: None,
+            strategy: Some("scan".to_string()),
+            dispatch: Some("destination".to_string()),
+        };
+
+        config.configure(&mut controller, &scenario).unwrap();
+        assert_eq!(controller.floors, Some(10));
+        assert_eq!(controller.mode, DispatchMode::Destination);
+        for elevator in &controller.elevators {
+            assert_eq!(lock(elevator, "elevator").unwrap().strategy.name(), "scan");
+        }
+        assert!(controller.in_service("A").unwrap());
+        assert!(!controller.in_service("B").unwrap());
+    }
+
+    #[test]
+    fn scan_runs_to_the_end_floor_where_look_turns_at_the_last_call() {
+        // Going up from floor 2, with only a down call at floor 5
+        let requests = [ButtonPressed::new_request(1, 5, 1)];
+
+        let look = sweep_next_stop(2, &requests, Direction::Up, None);
+        assert_eq!(look, Some((5, Direction::Down)));
+
+        let scan = sweep_next_stop(2, &requests, Direction::Up, Some(9));
+        assert_eq!(scan, Some((9, Direction::Down)));
+        let back = sweep_next_stop(9, &requests, Direction::Down, Some(9));
+        assert_eq!(back, Some((5, Direction::Down)));
+    }
+
+    #[test]
+    fn plan_leg_changes_cars_at_the_sky_lobby() {
+        let bank = |floors: std::ops::RangeInclusive<usize>| ElevatorConfig {
+            served_floors: floors.collect(),
+            ..ElevatorConfig::default()
+        };
+        let controller = GroupController::with_configs([("L", bank(0..=10)), ("H", bank(10..=20))]);
+
+        let leg = controller
+            .plan_leg(ButtonPressed::new_request(1, 4, 15))
+            .unwrap();
+        assert_eq!((leg.current_floor, leg.target_floor), (4, 10));
+        assert_eq!(leg.destination, Some(15));
+
+        let within_bank = ButtonPressed::new_request(2, 12, 19);
+        assert_eq!(controller.plan_leg(within_bank).unwrap(), within_bank);
+    }
+
+    #[test]
+    fn car_runs_a_stop_through_every_door_state() {
+        let mut elevator = Elevator::new_elevator("A".to_string(), 0);
+        for next in [
+            ElevatorState::MovingUp,
+            ElevatorState::DoorsOpening,
+            ElevatorState::DoorsOpen,
+            ElevatorState::DoorsClosing,
+            ElevatorState::Idle,
+        ] {
+            elevator.transition(next).unwrap();
+            assert_eq!(elevator.status, next);
+        }
+    }
+
+    #[test]
+    fn car_goes_out_of_service_and_back() {
+        let mut elevator = Elevator::new_elevator("A".to_string(), 0);
+        for next in [
+            ElevatorState::OutOfService,
+            ElevatorState::Maintenance,
+            ElevatorState::Idle,
+            ElevatorState::OutOfService,
+            ElevatorState::Idle,
+        ] {
+            elevator.transition(next).unwrap();
+            assert_eq!(elevator.status, next);
+        }
+        // Straight from out of service into a trip is not allowed
+        elevator.transition(ElevatorState::OutOfService).unwrap();
+        assert!(elevator.transition(ElevatorState::MovingUp).is_err());
+        assert_eq!(elevator.status, ElevatorState::OutOfService);
+    }
+
+    #[test]
+    fn moving_car_cannot_open_its_doors_outright() {
+        assert!(!ElevatorState::MovingUp.can_transition_to(ElevatorState::DoorsOpen));
+
+        let mut elevator = Elevator::new_elevator("A".to_string(), 0);
+        elevator.transition(ElevatorState::MovingUp).unwrap();
+        let e = elevator.transition(ElevatorState::DoorsOpen).unwrap_err();
+        assert_eq!(
+            e,
+            StateTransitionError {
+                elevator_id: "A".to_string(),
+                from: ElevatorState::MovingUp,
+                to: ElevatorState::DoorsOpen,
+            }
+        );
+        assert_eq!(elevator.status, ElevatorState::MovingUp);
+    }
 }
@@ -1,14 +1,40 @@
-use bma_benchmark::benchmark;
+use elevator_system::scenario::Scenario;
 use elevator_system::simulations::{
     concurrency_elevator_system::concurrency_elevator_system,
+    discrete_event_elevator_system::discrete_event_elevator_system,
     elevator_system_error_handling::elevator_system_error_handling,
     scheduling_elevator_system::scheduling_elevator_system,
 };
-use std::hint::black_box;
+use elevator_system::traffic::traffic_profile;
+use elevator_system::SimulationConfig;
 
 pub fn main() {
-    // concurrency_elevator_system();
-    // benchmark!(1, { scheduling_elevator_system() });
-    // scheduling_elevator_system();
-    elevator_system_error_handling();
+    let config = SimulationConfig::from_env();
+
+    // Optional scenario file or traffic profile after the simulation name
+    let default_scenario = Scenario::default();
+    let traffic = std::env::args().nth(2).and_then(|name| {
+        traffic_profile(&name, default_scenario.floors).map(|traffic| (name, traffic))
+    });
+    let scenario = match traffic {
+        Some((name, traffic)) => {
+            println!("Traffic profile {name} generated from seed {}", config.seed);
+            Ok(traffic.scenario(default_scenario.elevators, &mut config.rng()))
+        }
+        None => Scenario::from_args(2),
+    };
+    let scenario = match scenario {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            println!("****ERROR: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    match std::env::args().nth(1).as_deref() {
+        Some("concurrency") => concurrency_elevator_system(&config, &scenario),
+        Some("scheduling") => scheduling_elevator_system(&config, &scenario),
+        Some("discrete-event") => discrete_event_elevator_system(&config, &scenario),
+        _ => elevator_system_error_handling(&config, &scenario),
+    }
 }
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// A car taken out of service for a planned stretch of the run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub elevator_id: String,
+    /// From the start of the run.
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl MaintenanceWindow {
+    pub fn start(&self) -> Duration {
+        Duration::from_millis(self.start_ms)
+    }
+
+    pub fn end(&self) -> Duration {
+        Duration::from_millis(self.end_ms)
+    }
+}
+
+/// Cars out of service, and the maintenance windows still to come.
+///
+/// Any number of cars can be out at once; each comes back on its own. A car
+/// is held out by hand, by open windows, or both, and a window closing only
+/// brings it back once nothing else holds it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MaintenanceRegistry {
+    /// Cars taken out by hand rather than by a window.
+    held: BTreeSet<String>,
+    scheduled: Vec<MaintenanceWindow>,
+    active: Vec<MaintenanceWindow>,
+}
+
+/// Cars whose maintenance window opened or closed, from `MaintenanceRegistry::due`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MaintenanceChanges {
+    pub started: Vec<String>,
+    pub ended: Vec<String>,
+}
+
+impl MaintenanceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take `elevator_id` out of service until `end` is called for it, even
+    /// past the end of any window. Returns false if it already was out.
+    pub fn start(&mut self, elevator_id: &str) -> bool {
+        let was_out = self.contains(elevator_id);
+        self.held.insert(elevator_id.to_string());
+        !was_out
+    }
+
+    /// Put `elevator_id` back in service, closing its open windows early.
+    /// Returns false if it was not out.
+    pub fn end(&mut self, elevator_id: &str) -> bool {
+        let was_out = self.contains(elevator_id);
+        self.active
+            .retain(|window| window.elevator_id != elevator_id);
+        self.held.remove(elevator_id);
+        was_out
+    }
+
+    pub fn contains(&self, elevator_id: &str) -> bool {
+        self.held.contains(elevator_id)
+            || self
+                .active
+                .iter()
+                .any(|window| window.elevator_id == elevator_id)
+    }
+
+    pub fn out_of_service(&self) -> impl Iterator<Item = &str> {
+        let windows = self.active.iter().map(|window| window.elevator_id.as_str());
+        self.held
+            .iter()
+            .map(String::as_str)
+            .chain(windows)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+
+    pub fn schedule(&mut self, window: MaintenanceWindow) {
+        self.scheduled.push(window);
+    }
+
+    /// Windows not opened yet.
+    pub fn scheduled(&self) -> &[MaintenanceWindow] {
+        &self.scheduled
+    }
+
+    /// When the next window opens or an open one closes, if any is left.
+    pub fn next_change(&self) -> Option<Duration> {
+        self.scheduled
+            .iter()
+            .map(MaintenanceWindow::start)
+            .chain(self.active.iter().map(MaintenanceWindow::end))
+            .min()
+    }
+
+    /// Open and close the windows due by `now`, returning which cars went
+    /// out and which came back.
+    pub fn due(&mut self, now: Duration) -> MaintenanceChanges {
+        let mut changes = MaintenanceChanges::default();
+
+        let (opened, scheduled) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition::<Vec<_>, _>(|window| window.start() <= now);
+        self.scheduled = scheduled;
+        for window in opened {
+            if !self.contains(&window.elevator_id) {
+                changes.started.push(window.elevator_id.clone());
+            }
+            self.active.push(window);
+        }
+
+        let (closed, active) = std::mem::take(&mut self.active)
+            .into_iter()
+            .partition::<Vec<_>, _>(|window| window.end() <= now);
+        self.active = active;
+        for window in closed {
+            // Still held by hand or by another window
+            if !self.contains(&window.elevator_id) && !changes.ended.contains(&window.elevator_id) {
+                changes.ended.push(window.elevator_id);
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(elevator_id: &str, start_ms: u64, end_ms: u64) -> MaintenanceWindow {
+        MaintenanceWindow {
+            elevator_id: elevator_id.to_string(),
+            start_ms,
+            end_ms,
+        }
+    }
+
+    #[test]
+    fn closing_window_leaves_a_manual_hold_alone() {
+        let mut registry = MaintenanceRegistry::new();
+        assert!(registry.start("A"));
+        registry.schedule(window("A", 10, 20));
+
+        let opened = registry.due(Duration::from_millis(10));
+        assert!(opened.started.is_empty());
+        let closed = registry.due(Duration::from_millis(20));
+        assert!(closed.ended.is_empty());
+        assert!(registry.contains("A"));
+
+        assert!(registry.end("A"));
+        assert!(!registry.contains("A"));
+    }
+
+    #[test]
+    fn car_comes_back_when_its_last_window_closes() {
+        let mut registry = MaintenanceRegistry::new();
+        registry.schedule(window("A", 10, 30));
+        registry.schedule(window("A", 20, 40));
+
+        assert_eq!(registry.due(Duration::from_millis(20)).started, ["A"]);
+        assert!(registry.due(Duration::from_millis(30)).ended.is_empty());
+        assert_eq!(registry.due(Duration::from_millis(40)).ended, ["A"]);
+        assert_eq!(registry.out_of_service().count(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Limits of a car's drive: it speeds up and slows down along an S-curve,
+/// ramping acceleration at `jerk` up to `acceleration`, and cruises at
+/// `max_speed` when the trip is long enough to reach it.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct MotionProfile {
+    /// In metres per second.
+    pub max_speed: f64,
+    /// In metres per second squared.
+    pub acceleration: f64,
+    /// In metres per second cubed.
+    pub jerk: f64,
+}
+
+/// The speeding-up half of a trip; slowing down mirrors it.
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    peak_speed: f64,
+    peak_acceleration: f64,
+    /// Time spent changing acceleration, at each end of the ramp.
+    jerk_time: f64,
+    /// Time spent at `peak_acceleration`.
+    constant_time: f64,
+}
+
+impl Ramp {
+    fn duration(&self) -> f64 {
+        2.0 * self.jerk_time + self.constant_time
+    }
+
+    /// Distance covered by the whole ramp.
+    fn distance(&self) -> f64 {
+        // The velocity curve is point-symmetric about its midpoint
+        self.peak_speed * self.duration() / 2.0
+    }
+
+    /// Distance covered `t` seconds into the ramp.
+    fn distance_at(&self, jerk: f64, t: f64) -> f64 {
+        let (tj, ta, a) = (self.jerk_time, self.constant_time, self.peak_acceleration);
+        let t = t.clamp(0.0, self.duration());
+
+        if t < tj {
+            return jerk * t.powi(3) / 6.0;
+        }
+        let (v1, s1) = (jerk * tj * tj / 2.0, jerk * tj.powi(3) / 6.0);
+        if t < tj + ta {
+            let t = t - tj;
+            return s1 + v1 * t + a * t * t / 2.0;
+        }
+        let (v2, s2) = (v1 + a * ta, s1 + v1 * ta + a * ta * ta / 2.0);
+        let t = t - tj - ta;
+        s2 + v2 * t + a * t * t / 2.0 - jerk * t.powi(3) / 6.0
+    }
+}
+
+impl MotionProfile {
+    /// Fastest way to reach `peak_speed` from standstill.
+    fn ramp(&self, peak_speed: f64) -> Ramp {
+        let a = self.acceleration;
+        if peak_speed < a * a / self.jerk {
+            // Acceleration never gets to its limit
+            let peak_acceleration = (peak_speed * self.jerk).sqrt();
+            Ramp {
+                peak_speed,
+                peak_acceleration,
+                jerk_time: peak_acceleration / self.jerk,
+                constant_time: 0.0,
+            }
+        } else {
+            Ramp {
+                peak_speed,
+                peak_acceleration: a,
+                jerk_time: a / self.jerk,
+                constant_time: peak_speed / a - a / self.jerk,
+            }
+        }
+    }
+
+    /// The ramp used for a trip of `distance` metres: up to full speed if
+    /// there is room to slow down again, otherwise as fast as there is room for.
+    fn ramp_for(&self, distance: f64) -> Ramp {
+        let full = self.ramp(self.max_speed);
+        if 2.0 * full.distance() <= distance {
+            return full;
+        }
+
+        let (mut low, mut high) = (0.0, self.max_speed);
+        for _ in 0..64 {
+            let speed = (low + high) / 2.0;
+            if 2.0 * self.ramp(speed).distance() < distance {
+                low = speed;
+            } else {
+                high = speed;
+            }
+        }
+        self.ramp(low)
+    }
+
+    /// Time to travel `distance` metres from standstill to standstill.
+    pub fn travel_time(&self, distance: f64) -> Duration {
+        if distance <= 0.0 {
+            return Duration::ZERO;
+        }
+        let ramp = self.ramp_for(distance);
+        let cruise_time = (distance - 2.0 * ramp.distance()).max(0.0) / ramp.peak_speed;
+
+        Duration::from_secs_f64(2.0 * ramp.duration() + cruise_time)
+    }
+
+    /// Metres covered `elapsed` into a trip of `distance` metres.
+    pub fn distance_at(&self, distance: f64, elapsed: Duration) -> f64 {
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        let ramp = self.ramp_for(distance);
+        let total = self.travel_time(distance).as_secs_f64();
+        let t = elapsed.as_secs_f64().min(total);
+
+        if t <= ramp.duration() {
+            ramp.distance_at(self.jerk, t)
+        } else if t < total - ramp.duration() {
+            ramp.distance() + ramp.peak_speed * (t - ramp.duration())
+        } else {
+            distance - ramp.distance_at(self.jerk, total - t)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_trip_never_reaches_cruise_speed() {
+        let profile = MotionProfile {
+            max_speed: 2.0,
+            acceleration: 1.0,
+            jerk: 1.0,
+        };
+        // Reaching 2 m/s takes 3 m, and as much again to stop
+        let distance = 2.0;
+
+        let ramp = profile.ramp_for(distance);
+        assert!(ramp.peak_speed < profile.max_speed);
+        assert!((2.0 * ramp.distance() - distance).abs() < 1e-9);
+
+        let total = profile.travel_time(distance);
+        assert_eq!(total, Duration::from_secs_f64(2.0 * ramp.duration()));
+        assert!((profile.distance_at(distance, total / 2) - distance / 2.0).abs() < 1e-9);
+        assert!((profile.distance_at(distance, total) - distance).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,556 @@
+use crate::faults::{Fault, ScheduledFault};
+use crate::fire::{FireCommand, ScheduledFireCommand};
+use crate::maintenance::MaintenanceWindow;
+use crate::{ButtonPressed, ElevatorConfig, TimedRequest};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::Path,
+    time::Duration,
+};
+
+/// A building and the people using it, loaded from a JSON or CSV file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    /// Number of floors, numbered from 0.
+    pub floors: usize,
+    pub elevators: Vec<String>,
+    /// Cars that differ from `ElevatorConfig::default()`, by id.
+    #[serde(default)]
+    pub elevator_configs: BTreeMap<String, ElevatorConfig>,
+    pub requests: Vec<ScenarioRequest>,
+    /// Planned stretches during which a car is out of service.
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>,
+    /// Failures to inject into the error-handling simulation, and when.
+    #[serde(default)]
+    pub faults: Vec<ScheduledFault>,
+    /// Fire panel and firefighter commands, and when they are given.
+    #[serde(default)]
+    pub fire: Vec<ScheduledFireCommand>,
+}
+
+/// One passenger of a scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioRequest {
+    /// When the button is pressed, in milliseconds from the start of the run.
+    /// Without it the simulation picks its own gap after the previous request.
+    #[serde(default)]
+    pub at_ms: Option<u64>,
+    pub person_id: usize,
+    pub current_floor: usize,
+    pub target_floor: usize,
+}
+
+impl ScenarioRequest {
+    pub fn button_pressed(&self) -> ButtonPressed {
+        ButtonPressed::new_request(self.person_id, self.current_floor, self.target_floor)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Csv { line: usize, message: String },
+    UnknownFormat(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "cannot read scenario: {e}"),
+            ScenarioError::Json(e) => write!(f, "invalid scenario JSON: {e}"),
+            ScenarioError::Csv { line, message } => {
+                write!(f, "invalid scenario CSV at line {line}: {message}")
+            }
+            ScenarioError::UnknownFormat(path) => {
+                write!(f, "{path}: scenario files must end in .json or .csv")
+            }
+            ScenarioError::Invalid(message) => write!(f, "invalid scenario: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(e: std::io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(e: serde_json::Error) -> Self {
+        ScenarioError::Json(e)
+    }
+}
+
+impl Default for Scenario {
+    /// The seven-passenger, two-car building the simulations were written for.
+    fn default() -> Self {
+        let requests = [
+            (1, 0, 5),
+            (2, 1, 4),
+            (3, 1, 3),
+            (4, 2, 6),
+            (5, 2, 0),
+            (6, 5, 3),
+            (7, 5, 2),
+        ]
+        .into_iter()
+        .map(|(person_id, current_floor, target_floor)| ScenarioRequest {
+            at_ms: None,
+            person_id,
+            current_floor,
+            target_floor,
+        })
+        .collect();
+
+        Scenario {
+            floors: 7,
+            elevators: vec!["A".to_string(), "B".to_string()],
+            elevator_configs: BTreeMap::new(),
+            requests,
+            maintenance: Vec::new(),
+            faults: Vec::new(),
+            fire: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    /// Load a scenario, picking the format from the file extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let scenario = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&contents)?,
+            Some("csv") => Self::from_csv(&contents)?,
+            _ => return Err(ScenarioError::UnknownFormat(path.display().to_string())),
+        };
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    /// Load the scenario named by the command line argument at `index`, or
+    /// the default one when it is missing.
+    pub fn from_args(index: usize) -> Result<Self, ScenarioError> {
+        match std::env::args().nth(index) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, ScenarioError> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Parse the CSV form of a scenario:
+    ///
+    /// ```text
+    /// # comment
+    /// floors,7
+    /// elevators,A,B
+    /// elevator,F,rated_persons=10,rated_load_kg=2000,max_speed=0.5,served_floors=0;3;6
+    /// request,<at_ms>,<person_id>,<current_floor>,<target_floor>
+    /// maintenance,<elevator_id>,<start_ms>,<end_ms>
+    /// fault,<at_ms>,door_jam,<elevator_id>,<floor>,<duration_ms>
+    /// fault,<at_ms>,stuck,<elevator_id>,<duration_ms>
+    /// fault,<at_ms>,wrong_floor,<elevator_id>,<reported_floor>
+    /// fault,<at_ms>,message_loss,<count>
+    /// fault,<at_ms>,worker_panic,<elevator_id>
+    /// fault,<at_ms>,poison_lock,<elevator_id>
+    /// fault,<at_ms>,maintenance,<elevator_id>,<duration_ms>
+    /// fault,<at_ms>,power_outage,<duration_ms>
+    /// fire,<at_ms>,recall,<floor>
+    /// fire,<at_ms>,take_control,<elevator_id>,<person_id>
+    /// fire,<at_ms>,car_call,<elevator_id>,<person_id>,<floor>
+    /// fire,<at_ms>,release,<elevator_id>
+    /// fire,<at_ms>,reset
+    /// ```
+    ///
+    /// An `elevator` row sets any `ElevatorConfig` fields of one car and adds
+    /// the car if it was not listed yet. `served_floors` also takes ranges,
+    /// e.g. `0-10;20`. `at_ms` may be left empty to let the
+    /// simulation pick the arrival gap.
+    pub fn from_csv(contents: &str) -> Result<Self, ScenarioError> {
+        let mut floors = None;
+        let mut elevators = Vec::new();
+        let mut elevator_configs = BTreeMap::new();
+        let mut requests = Vec::new();
+        let mut maintenance = Vec::new();
+        let mut faults = Vec::new();
+        let mut fire = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let number = |field: &str| {
+                field.parse::<usize>().map_err(|_| ScenarioError::Csv {
+                    line: line_number,
+                    message: format!("`{field}` is not a number"),
+                })
+            };
+
+            match fields.as_slice() {
+                ["floors", count] => floors = Some(number(count)?),
+                ["elevators", ids @ ..] => {
+                    elevators.extend(ids.iter().map(|id| id.to_string()));
+                }
+                ["elevator", id, settings @ ..] => {
+                    let config =
+                        elevator_config(settings).map_err(|message| ScenarioError::Csv {
+                            line: line_number,
+                            message,
+                        })?;
+                    if !elevators.iter().any(|e| e == id) {
+                        elevators.push(id.to_string());
+                    }
+                    elevator_configs.insert(id.to_string(), config);
+                }
+                ["request", at_ms, person_id, current_floor, target_floor] => {
+                    let at_ms = match *at_ms {
+                        "" => None,
+                        at_ms => Some(number(at_ms)? as u64),
+                    };
+                    requests.push(ScenarioRequest {
+                        at_ms,
+                        person_id: number(person_id)?,
+                        current_floor: number(current_floor)?,
+                        target_floor: number(target_floor)?,
+                    });
+                }
+                ["maintenance", elevator_id, start_ms, end_ms] => {
+                    maintenance.push(MaintenanceWindow {
+                        elevator_id: elevator_id.to_string(),
+                        start_ms: number(start_ms)? as u64,
+                        end_ms: number(end_ms)? as u64,
+                    });
+                }
+                ["fault", at_ms, fault @ ..] => {
+                    faults.push(ScheduledFault {
+                        at_ms: number(at_ms)? as u64,
+                        fault: Fault::from_csv(fault).map_err(|message| ScenarioError::Csv {
+                            line: line_number,
+                            message,
+                        })?,
+                    });
+                }
+                ["fire", at_ms, command @ ..] => {
+                    fire.push(ScheduledFireCommand {
+                        at_ms: number(at_ms)? as u64,
+                        command: FireCommand::from_csv(command).map_err(|message| {
+                            ScenarioError::Csv {
+                                line: line_number,
+                                message,
+                            }
+                        })?,
+                    });
+                }
+                _ => {
+                    return Err(ScenarioError::Csv {
+                        line: line_number,
+                        message: format!("unrecognised row `{line}`"),
+                    })
+                }
+            }
+        }
+
+        let floors = floors.ok_or(ScenarioError::Csv {
+            line: contents.lines().count(),
+            message: "missing `floors` row".to_string(),
+        })?;
+
+        Ok(Scenario {
+            floors,
+            elevators,
+            elevator_configs,
+            requests,
+            maintenance,
+            faults,
+            fire,
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if self.floors == 0 {
+            return Err(ScenarioError::Invalid("building has no floors".to_string()));
+        }
+        if self.elevators.is_empty() {
+            return Err(ScenarioError::Invalid(
+                "building has no elevators".to_string(),
+            ));
+        }
+        let mut elevator_ids = BTreeSet::new();
+        if let Some(id) = self.elevators.iter().find(|id| !elevator_ids.insert(*id)) {
+            return Err(ScenarioError::Invalid(format!(
+                "elevator {id} is listed twice"
+            )));
+        }
+        let mut person_ids = BTreeSet::new();
+        if let Some(request) = self
+            .requests
+            .iter()
+            .find(|request| !person_ids.insert(request.person_id))
+        {
+            return Err(ScenarioError::Invalid(format!(
+                "person {} makes more than one request",
+                request.person_id
+            )));
+        }
+        for request in &self.requests {
+            request
+                .button_pressed()
+                .validate(Some(self.floors))
+                .map_err(|e| ScenarioError::Invalid(e.to_string()))?;
+        }
+        for window in &self.maintenance {
+            if !self.elevators.contains(&window.elevator_id) {
+                return Err(ScenarioError::Invalid(format!(
+                    "maintenance of unknown elevator {}",
+                    window.elevator_id
+                )));
+            }
+            if window.start_ms >= window.end_ms {
+                return Err(ScenarioError::Invalid(format!(
+                    "maintenance of elevator {} ends before it starts",
+                    window.elevator_id
+                )));
+            }
+        }
+        for ScheduledFault { fault, .. } in &self.faults {
+            let invalid =
+                |problem: &str| Err(ScenarioError::Invalid(format!("{fault}: {problem}")));
+            if fault
+                .elevator_id()
+                .is_some_and(|elevator_id| !self.elevators.iter().any(|e| e == elevator_id))
+            {
+                return invalid("unknown elevator");
+            }
+            match fault {
+                Fault::DoorJam { floor, .. }
+                | Fault::WrongFloor {
+                    reported_floor: floor,
+                    ..
+                } if *floor >= self.floors => {
+                    return invalid("the building does not have that floor");
+                }
+                Fault::DoorJam { duration_ms: 0, .. }
+                | Fault::Stuck { duration_ms: 0, .. }
+                | Fault::Maintenance { duration_ms: 0, .. }
+                | Fault::PowerOutage { duration_ms: 0 } => {
+                    return invalid("does not last at all");
+                }
+                Fault::MessageLoss { count: 0 } => return invalid("loses no message"),
+                _ => {}
+            }
+        }
+        for ScheduledFireCommand { command, .. } in &self.fire {
+            let invalid =
+                |problem: &str| Err(ScenarioError::Invalid(format!("{command}: {problem}")));
+            if command
+                .elevator_id()
+                .is_some_and(|elevator_id| !self.elevators.iter().any(|e| e == elevator_id))
+            {
+                return invalid("unknown elevator");
+            }
+            if command.floor().is_some_and(|floor| floor >= self.floors) {
+                return invalid("the building does not have that floor");
+            }
+        }
+        for (id, config) in &self.elevator_configs {
+            let invalid =
+                |problem: &str| Err(ScenarioError::Invalid(format!("elevator {id} {problem}")));
+            if !self.elevators.contains(id) {
+                return invalid("is configured but not listed in `elevators`");
+            }
+            if config.capacity() == 0 {
+                return invalid("cannot take a single passenger");
+            }
+            // NaN is not greater than zero either
+            let positive = [
+                config.max_speed,
+                config.acceleration,
+                config.jerk,
+                config.floor_height,
+                config.battery_speed,
+            ]
+            .iter()
+            .all(|x| *x > 0.0);
+            if !positive {
+                return invalid(
+                    "needs a positive speed, battery speed, acceleration, jerk and floor height",
+                );
+            }
+            let floors = config
+                .served_floors
+                .iter()
+                .chain(&config.home_floor)
+                .chain(&config.evacuation_floor);
+            if floors.clone().any(|floor| *floor >= self.floors) {
+                return invalid("serves a floor the building does not have");
+            }
+            if config
+                .home_floor
+                .is_some_and(|home_floor| !config.serves(home_floor))
+            {
+                return invalid("has a home floor it does not serve");
+            }
+            if config
+                .evacuation_floor
+                .is_some_and(|evacuation_floor| !config.serves(evacuation_floor))
+            {
+                return invalid("has an evacuation floor it does not serve");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every car with its config, in the order of `elevators`.
+    pub fn cars(&self) -> Vec<(String, ElevatorConfig)> {
+        self.elevators
+            .iter()
+            .map(|id| {
+                let config = self.elevator_configs.get(id).cloned().unwrap_or_default();
+                (id.clone(), config)
+            })
+            .collect()
+    }
+
+    pub fn top_floor(&self) -> usize {
+        self.floors.saturating_sub(1)
+    }
+
+    pub fn button_presses(&self) -> Vec<ButtonPressed> {
+        self.requests
+            .iter()
+            .map(ScenarioRequest::button_pressed)
+            .collect()
+    }
+
+    /// Requests with absolute arrival times. Wherever the scenario gives no
+    /// time, the request comes `gap()` after the previous one (or after the
+    /// start of the run).
+    pub fn timed_requests(&self, mut gap: impl FnMut() -> Duration) -> Vec<TimedRequest> {
+        let mut at = Duration::ZERO;
+
+        self.requests
+            .iter()
+            .map(|request| {
+                at = match request.at_ms {
+                    Some(at_ms) => Duration::from_millis(at_ms),
+                    None => at + gap(),
+                };
+                TimedRequest {
+                    at,
+                    request: request.button_pressed(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Build a car config from `key=value` CSV fields, starting from the default.
+fn elevator_config(settings: &[&str]) -> Result<ElevatorConfig, String> {
+    fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+        value
+            .parse()
+            .map_err(|_| format!("`{value}` is not a valid {key}"))
+    }
+
+    let mut config = ElevatorConfig::default();
+    for setting in settings {
+        let Some((key, value)) = setting.split_once('=') else {
+            return Err(format!("`{setting}` is not a key=value setting"));
+        };
+        match key {
+            "rated_persons" => config.rated_persons = parse(key, value)?,
+            "rated_load_kg" => config.rated_load_kg = parse(key, value)?,
+            "max_speed" => config.max_speed = parse(key, value)?,
+            "acceleration" => config.acceleration = parse(key, value)?,
+            "jerk" => config.jerk = parse(key, value)?,
+            "floor_height" => config.floor_height = parse(key, value)?,
+            "door_open_ms" => config.door_open_ms = parse(key, value)?,
+            "door_dwell_ms" => config.door_dwell_ms = parse(key, value)?,
+            "door_close_ms" => config.door_close_ms = parse(key, value)?,
+            "passenger_transfer_ms" => config.passenger_transfer_ms = parse(key, value)?,
+            "battery_speed" => config.battery_speed = parse(key, value)?,
+            "evacuation_floor" => config.evacuation_floor = Some(parse(key, value)?),
+            "served_floors" => {
+                config.served_floors.clear();
+                for floors in value.split(';') {
+                    match floors.split_once('-') {
+                        Some((first, last)) => {
+                            let (first, last) = (parse::<usize>(key, first)?, parse(key, last)?);
+                            // An empty list would mean every floor
+                            if first > last {
+                                return Err(format!("floor range `{floors}` runs backwards"));
+                            }
+                            config.served_floors.extend(first..=last);
+                        }
+                        None => config.served_floors.push(parse(key, floors)?),
+                    }
+                }
+            }
+            "home_floor" => config.home_floor = Some(parse(key, value)?),
+            _ => return Err(format!("unknown elevator setting `{key}`")),
+        }
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_duplicate_ids_and_nan_speeds() {
+        assert!(Scenario::default().validate().is_ok());
+
+        let mut scenario = Scenario::default();
+        scenario.elevators.push("A".to_string());
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = Scenario::default();
+        scenario.requests[1].person_id = scenario.requests[0].person_id;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = Scenario::default();
+        let config = ElevatorConfig {
+            max_speed: f64::NAN,
+            ..ElevatorConfig::default()
+        };
+        scenario.elevator_configs.insert("A".to_string(), config);
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn reversed_served_floor_range_is_rejected() {
+        let scenario = |served_floors| {
+            Scenario::from_csv(&format!(
+                "floors,21\nelevator,L,served_floors={served_floors}\n"
+            ))
+        };
+
+        let served = scenario("0-3;20").unwrap().elevator_configs["L"]
+            .served_floors
+            .clone();
+        assert_eq!(served, [0, 1, 2, 3, 20]);
+        assert!(matches!(
+            scenario("10-0"),
+            Err(ScenarioError::Csv { line: 2, .. })
+        ));
+    }
+}
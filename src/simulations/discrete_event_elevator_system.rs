@@ -0,0 +1,557 @@
+use crate::events::SimEvent;
+use crate::faults::{Fault, ScheduledFault};
+use crate::maintenance::MaintenanceWindow;
+use crate::scenario::Scenario;
+use crate::{
+    lock, ButtonPressed, Clock, ElevatorError, GroupController, Recovery, SimulationConfig,
+    TimedRequest,
+};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, BinaryHeap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long someone whose call was lost waits before pressing again.
+const PRESS_AGAIN_AFTER: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+enum SimulatorEvent {
+    /// A person arrives and presses the hall button.
+    Arrival(ButtonPressed),
+    /// Car `index` finished its last stop and can act again.
+    CarReady(usize),
+    /// A maintenance window opens or closes.
+    MaintenanceDue,
+    Fault(Fault),
+    FaultCleared(Fault),
+}
+
+#[derive(Debug)]
+struct ScheduledEvent {
+    at: Duration,
+    // Insertion order, so events due at the same time run first come first served.
+    seq: u64,
+    event: SimulatorEvent,
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+/// Single-threaded simulator that drives the cars of a `GroupController`
+/// from a queue of events ordered by virtual time.
+///
+/// Nothing sleeps: the clock jumps straight to the next event, and each stop
+/// a car makes takes as long as its cost model says. The same inputs always
+/// give the same run.
+pub struct DiscreteEventSimulator {
+    pub now: Duration,
+    pub controller: GroupController,
+    events: BinaryHeap<Reverse<ScheduledEvent>>,
+    next_seq: u64,
+    busy: Vec<bool>,
+    /// Times a `MaintenanceDue` is already queued for.
+    maintenance_due: BTreeSet<Duration>,
+    /// Calls still to be lost from an injected message loss.
+    lost_calls: usize,
+}
+
+impl DiscreteEventSimulator {
+    pub fn new(mut controller: GroupController) -> Result<Self, ElevatorError> {
+        controller.set_clock(Clock::virtual_time())?;
+        let busy = vec![false; controller.elevators.len()];
+
+        Ok(DiscreteEventSimulator {
+            now: Duration::ZERO,
+            controller,
+            events: BinaryHeap::new(),
+            next_seq: 0,
+            busy,
+            maintenance_due: BTreeSet::new(),
+            lost_calls: 0,
+        })
+    }
+
+    pub fn schedule_request(&mut self, timed_request: TimedRequest) {
+        self.schedule(
+            timed_request.at,
+            SimulatorEvent::Arrival(timed_request.request),
+        );
+    }
+
+    /// Plan a maintenance window, to be opened and closed in virtual time.
+    pub fn schedule_maintenance(&mut self, window: MaintenanceWindow) -> Result<(), ElevatorError> {
+        let scheduled = self.controller.schedule_maintenance(window);
+        self.follow_maintenance();
+        scheduled
+    }
+
+    /// Inject a fault in virtual time, and clear it once it has run its course.
+    pub fn schedule_fault(&mut self, scheduled: ScheduledFault) {
+        let clears_at = scheduled
+            .fault
+            .clears_after()
+            .map(|clears_after| scheduled.at() + clears_after);
+        self.schedule(
+            scheduled.at(),
+            SimulatorEvent::Fault(scheduled.fault.clone()),
+        );
+        if let Some(clears_at) = clears_at {
+            self.schedule(clears_at, SimulatorEvent::FaultCleared(scheduled.fault));
+        }
+    }
+
+    /// Queue a `MaintenanceDue` for the next window to open or close,
+    /// including the windows the controller opens itself for a fault.
+    fn follow_maintenance(&mut self) {
+        let next_change = lock(&self.controller.maintenance, "maintenance")
+            .ok()
+            .and_then(|maintenance| maintenance.next_change());
+        if let Some(at) = next_change.filter(|at| self.maintenance_due.insert(*at)) {
+            self.schedule(at.max(self.now), SimulatorEvent::MaintenanceDue);
+        }
+    }
+
+    fn schedule(&mut self, at: Duration, event: SimulatorEvent) {
+        self.events.push(Reverse(ScheduledEvent {
+            at,
+            seq: self.next_seq,
+            event,
+        }));
+        self.next_seq += 1;
+    }
+
+    /// Process events until none are left, returning the final virtual time.
+    pub fn run(&mut self) -> Duration {
+        // Windows scheduled straight on the controller
+        self.follow_maintenance();
+        while let Some(Reverse(scheduled)) = self.events.pop() {
+            self.now = scheduled.at;
+            self.controller.clock.set(self.now);
+            match scheduled.event {
+                SimulatorEvent::Arrival(button_pressed) => self.arrival(button_pressed),
+                SimulatorEvent::MaintenanceDue => self.maintenance_due(),
+                SimulatorEvent::CarReady(index) => {
+                    // A panic in the car's step poisons its lock, as it would
+                    // in the car's worker thread
+                    let ready = panic::catch_unwind(AssertUnwindSafe(|| self.car_ready(index)))
+                        .unwrap_or_else(|panic| Err(ElevatorError::worker_panicked(panic)));
+                    if let Err(e) = ready {
+                        self.car_failed(index, e);
+                    }
+                }
+                SimulatorEvent::Fault(fault) => {
+                    if let Fault::MessageLoss { count } = fault {
+                        self.lost_calls += count;
+                    }
+                    let injected = self.controller.inject_fault(&fault);
+                    self.faults_changed(injected);
+                }
+                SimulatorEvent::FaultCleared(fault) => {
+                    let cleared = self.controller.clear_fault(&fault);
+                    self.faults_changed(cleared);
+                }
+            }
+            self.take_transfers();
+            self.follow_maintenance();
+        }
+
+        self.now
+    }
+
+    fn arrival(&mut self, button_pressed: ButtonPressed) {
+        if self.lost_calls > 0 {
+            self.lost_calls -= 1;
+            self.controller.publish(SimEvent::MessageLost {
+                message: format!("call of person {}", button_pressed.person_id),
+            });
+            // Nobody answers a lost call, so the person presses again
+            self.schedule(
+                self.now + PRESS_AGAIN_AFTER,
+                SimulatorEvent::Arrival(button_pressed),
+            );
+            return;
+        }
+        let _ = self.controller.submit(button_pressed);
+
+        // Wake every idle car so it can look at the new call
+        self.wake_idle_cars();
+    }
+
+    fn maintenance_due(&mut self) {
+        if let Err(e) = self.controller.apply_maintenance_schedule() {
+            self.controller.publish(SimEvent::Error {
+                elevator_id: None,
+                message: e.to_string(),
+            });
+        }
+
+        // Cars back in service, or left to cover for one that went out
+        self.wake_idle_cars();
+    }
+
+    /// Car `index` failed with `e`: take it out if need be, and let the other
+    /// cars pick up its calls. A car that can retry waits for the next call.
+    fn car_failed(&mut self, index: usize, e: ElevatorError) {
+        self.controller.publish(SimEvent::Error {
+            elevator_id: None,
+            message: e.to_string(),
+        });
+        match e.recovery() {
+            Recovery::Retry => self.busy[index] = false,
+            Recovery::IsolateCar => {
+                if let Err(e) = self.controller.isolate_car(index, &e) {
+                    self.controller.publish(SimEvent::Error {
+                        elevator_id: None,
+                        message: e.to_string(),
+                    });
+                }
+                // Woken again once it is back in service, if ever
+                self.busy[index] = false;
+                self.wake_idle_cars();
+            }
+            // Never woken again
+            Recovery::Shutdown => self.busy[index] = true,
+        }
+    }
+
+    /// A fault was injected or cleared: isolate the cars it failed and wake
+    /// the ones left to serve.
+    fn faults_changed(&mut self, changed: Result<(), ElevatorError>) {
+        if let Err(e) = changed {
+            self.controller.publish(SimEvent::Error {
+                elevator_id: None,
+                message: e.to_string(),
+            });
+            if e.recovery() == Recovery::IsolateCar {
+                if let Err(e) = self.controller.isolate_failed(&e) {
+                    self.controller.publish(SimEvent::Error {
+                        elevator_id: None,
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+        self.wake_idle_cars();
+    }
+
+    /// Register the next leg of everyone who got off a car to change cars,
+    /// including riders let out of a car taken out of service.
+    fn take_transfers(&mut self) {
+        for index in 0..self.busy.len() {
+            let Ok(mut elevator) = self.controller.elevators[index].lock() else {
+                continue;
+            };
+            let transfers = std::mem::take(&mut elevator.transfers);
+            drop(elevator);
+
+            // People changing cars press the hall button again on arrival
+            for next_leg in transfers {
+                let at = next_leg.transferred_at.unwrap_or(self.now).max(self.now);
+                self.schedule(at, SimulatorEvent::Arrival(next_leg));
+            }
+        }
+    }
+
+    fn wake_idle_cars(&mut self) {
+        for index in 0..self.busy.len() {
+            // Cars out of service stay parked until they come back
+            let in_service = self.controller.elevators[index]
+                .lock()
+                .is_ok_and(|elevator| elevator.in_service().unwrap_or(false));
+            if !self.busy[index] && in_service {
+                self.busy[index] = true;
+                self.schedule(self.now, SimulatorEvent::CarReady(index));
+            }
+        }
+    }
+
+    fn car_ready(&mut self, index: usize) -> Result<(), ElevatorError> {
+        let hall_calls = Arc::clone(self.controller.hall_calls(index));
+        let elevator_id = lock(&self.controller.elevators[index], "elevator")?
+            .id
+            .clone();
+        if !self.controller.in_service(&elevator_id)? {
+            // Taken out mid-trip: hand back what it has not served and park
+            self.controller.evacuate(index)?;
+            self.busy[index] = false;
+            // Someone else picks up what it handed back
+            self.wake_idle_cars();
+            return Ok(());
+        }
+        let mut elevator = lock(&self.controller.elevators[index], "elevator")?;
+
+        if elevator.requests.is_empty() {
+            // Trip over: fetch the next batch, if anyone is waiting
+            if let Some(request_queue) = elevator.process_requests(lock(&hall_calls, "hall calls")?)
+            {
+                let direction = request_queue[0].direction();
+                self.controller.publish(SimEvent::RequestsTaken {
+                    elevator_id: elevator.id.clone(),
+                    person_ids: request_queue.iter().map(|r| r.person_id).collect(),
+                });
+                elevator.assign_requests(request_queue.into(), direction);
+            }
+        }
+
+        match elevator.step(&hall_calls)? {
+            Some(elapsed) => {
+                drop(elevator);
+                self.schedule(self.now + elapsed, SimulatorEvent::CarReady(index));
+            }
+            None => {
+                // Nothing to do and parked: sleep until the next arrival
+                self.busy[index] = false;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn discrete_event_elevator_system(config: &SimulationConfig, scenario: &Scenario) {
+    println!("Simulation seed: {}", config.seed);
+
+    // Configured on the virtual clock, so the maintenance windows open on it
+    let controller = GroupController::with_configs(scenario.cars());
+    let simulator = DiscreteEventSimulator::new(controller).and_then(|mut simulator| {
+        config.configure(&mut simulator.controller, scenario)?;
+        Ok(simulator)
+    });
+    let mut simulator = match simulator {
+        Ok(simulator) => simulator,
+        Err(e) => {
+            println!("****ERROR: {e}");
+            return;
+        }
+    };
+    for scheduled in &scenario.faults {
+        simulator.schedule_fault(scheduled.clone());
+    }
+
+    // Without a time in the scenario, people arrive ten seconds apart
+    for timed_request in scenario.timed_requests(|| Duration::from_secs(10)) {
+        simulator.schedule_request(timed_request);
+    }
+
+    let finished_at = simulator.run();
+    println!("Simulation finished at {:?} of building time", finished_at);
+    match simulator.controller.report() {
+        Ok(report) => print!("{report}"),
+        Err(e) => println!("****ERROR: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventRecorder;
+    use crate::traffic::traffic_profile;
+    use crate::DispatchMode;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Person 1 from floor 0 and person 2 from floor 1 ride up in car A, and
+    /// person 3 calls a car down 20 s in, with `fault` injected at `at_ms`.
+    fn run_with_fault(at_ms: u64, fault: Fault) -> (GroupController, Vec<SimEvent>) {
+        let scenario = Scenario::from_csv(
+            "floors,10\n\
+             elevators,A,B\n\
+             request,0,1,0,9\n\
+             request,0,2,1,8\n\
+             request,20000,3,5,0\n",
+        )
+        .unwrap();
+        let mut controller = GroupController::with_configs(scenario.cars());
+        controller.floors = Some(scenario.floors);
+        let events = Arc::new(EventRecorder::new());
+        controller.set_event_sink(events.clone()).unwrap();
+        let mut simulator = DiscreteEventSimulator::new(controller).unwrap();
+        simulator.schedule_fault(ScheduledFault { at_ms, fault });
+        for timed_request in scenario.timed_requests(|| Duration::from_secs(10)) {
+            simulator.schedule_request(timed_request);
+        }
+
+        simulator.run();
+        let events = events.events().unwrap().into_iter();
+        (
+            simulator.controller,
+            events.map(|(_, event)| event).collect(),
+        )
+    }
+
+    /// The car that took `person_id` the last stretch to `floor`, and
+    /// whether they had been let out of another car short of it.
+    fn delivery(controller: &GroupController, person_id: usize, floor: usize) -> (String, bool) {
+        controller
+            .elevators
+            .iter()
+            .find_map(|elevator| {
+                let elevator = lock(elevator, "elevator").unwrap();
+                elevator
+                    .completed
+                    .iter()
+                    .find(|leg| leg.person_id == person_id && leg.target_floor == floor)
+                    .map(|leg| (elevator.id.clone(), leg.transferred_at.is_some()))
+            })
+            .unwrap_or_else(|| panic!("person {person_id} never got to floor {floor}"))
+    }
+
+    fn isolated(events: &[SimEvent], elevator_id: &str) -> bool {
+        events.iter().any(|event| {
+            matches!(event, SimEvent::CarIsolated { elevator_id: id, .. } if id == elevator_id)
+        })
+    }
+
+    #[test]
+    fn jammed_car_is_isolated_until_its_doors_are_freed() {
+        let (controller, events) = run_with_fault(
+            0,
+            Fault::DoorJam {
+                elevator_id: "A".to_string(),
+                floor: 1,
+                duration_ms: 5000,
+            },
+        );
+
+        assert!(isolated(&events, "A"));
+        // Both riders got out at floor 1 and were taken on by car B
+        assert_eq!(delivery(&controller, 1, 9), ("B".to_string(), true));
+        assert_eq!(delivery(&controller, 2, 8), ("B".to_string(), true));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SimEvent::ReturnedToService { elevator_id } if elevator_id == "A"
+        )));
+        assert!(!delivery(&controller, 3, 0).1);
+    }
+
+    #[test]
+    fn stuck_car_is_taken_out_until_its_riders_are_rescued() {
+        let (controller, events) = run_with_fault(
+            5000,
+            Fault::Stuck {
+                elevator_id: "A".to_string(),
+                duration_ms: 10000,
+            },
+        );
+
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SimEvent::CarStuck { elevator_id, .. } if elevator_id == "A"
+        )));
+        // Both riders are rescued at floor 1 and carry on with whichever
+        // car is free, while car B serves the later call
+        assert!(delivery(&controller, 1, 9).1);
+        assert!(delivery(&controller, 2, 8).1);
+        assert_eq!(delivery(&controller, 3, 0), ("B".to_string(), false));
+    }
+
+    #[test]
+    fn car_that_misread_its_floor_is_isolated_and_finds_it_again() {
+        let (controller, events) = run_with_fault(
+            15000,
+            Fault::WrongFloor {
+                elevator_id: "A".to_string(),
+                reported_floor: 4,
+            },
+        );
+
+        assert!(isolated(&events, "A"));
+        // Person 1 got out at floor 8, where car A noticed, and called again
+        assert!(delivery(&controller, 1, 9).1);
+        assert_eq!(delivery(&controller, 2, 8), ("A".to_string(), false));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SimEvent::CarArrived { elevator_id, floor: 0, .. } if elevator_id == "A"
+        )));
+        assert_eq!(delivery(&controller, 3, 0), ("B".to_string(), false));
+    }
+
+    #[test]
+    fn lost_calls_are_made_again() {
+        let (controller, events) = run_with_fault(0, Fault::MessageLoss { count: 2 });
+
+        let lost = events
+            .iter()
+            .filter(|event| matches!(event, SimEvent::MessageLost { .. }))
+            .count();
+        assert_eq!(lost, 2);
+        for (person_id, floor) in [(1, 9), (2, 8), (3, 0)] {
+            delivery(&controller, person_id, floor);
+        }
+    }
+
+    #[test]
+    fn car_whose_worker_panics_is_isolated_for_good() {
+        let (controller, events) = run_with_fault(
+            5000,
+            Fault::WorkerPanic {
+                elevator_id: "A".to_string(),
+            },
+        );
+
+        assert!(isolated(&events, "A"));
+        // Car B takes everyone car A was carrying, and the later call too
+        assert_eq!(delivery(&controller, 1, 9), ("B".to_string(), true));
+        assert_eq!(delivery(&controller, 2, 8), ("B".to_string(), true));
+        assert_eq!(delivery(&controller, 3, 0), ("B".to_string(), false));
+        assert!(!controller.in_service("A").unwrap());
+    }
+
+    #[test]
+    fn same_scenario_gives_the_same_kpis() {
+        let traffic = traffic_profile("lunch", 10).unwrap();
+        let elevators = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let scenario = traffic.scenario(elevators, &mut StdRng::seed_from_u64(3));
+        let run = || {
+            let controller = GroupController::with_configs(scenario.cars());
+            let mut simulator = DiscreteEventSimulator::new(controller).unwrap();
+            for timed_request in scenario.timed_requests(|| Duration::from_secs(10)) {
+                simulator.schedule_request(timed_request);
+            }
+            simulator.run();
+            simulator.controller.report().unwrap()
+        };
+
+        let report = run();
+        assert_eq!(report.fleet.passengers, scenario.requests.len());
+        assert_eq!(report, run());
+    }
+
+    #[test]
+    fn destination_dispatch_delivers_every_passenger() {
+        let scenario = Scenario::default();
+        let mut controller = GroupController::with_configs(scenario.cars());
+        controller.floors = Some(scenario.floors);
+        controller.mode = DispatchMode::Destination;
+        let mut simulator = DiscreteEventSimulator::new(controller).unwrap();
+        for timed_request in scenario.timed_requests(|| Duration::from_secs(10)) {
+            simulator.schedule_request(timed_request);
+        }
+
+        simulator.run();
+        let report = simulator.controller.report().unwrap();
+        assert_eq!(report.fleet.passengers, scenario.requests.len());
+        // Every call went straight to the car it was given
+        assert!(lock(&simulator.controller.button_press_queue, "hall calls")
+            .unwrap()
+            .is_empty());
+        assert!(report.cars.iter().all(|car| car.journeys.passengers > 0));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod concurrency_elevator_system;
+pub mod discrete_event_elevator_system;
 pub mod elevator_system_error_handling;
 pub mod scheduling_elevator_system;
@@ -0,0 +1,332 @@
+use crate::scenario::{Scenario, ScenarioRequest};
+use crate::{ButtonPressed, RequestError, TimedRequest};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::time::Duration;
+
+/// Relative demand between every pair of floors: `weights[origin][destination]`.
+///
+/// Weights need not add up to one; only their ratios matter. Trips from a
+/// floor to itself are never generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginDestination {
+    weights: Vec<Vec<f64>>,
+}
+
+impl OriginDestination {
+    /// No demand at all; fill it in with `set`.
+    pub fn empty(floors: usize) -> Self {
+        OriginDestination {
+            weights: vec![vec![0.0; floors]; floors],
+        }
+    }
+
+    pub fn floors(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Set the demand from `origin` to `destination`. Trips from a floor to
+    /// itself are left out.
+    pub fn set(
+        &mut self,
+        origin: usize,
+        destination: usize,
+        weight: f64,
+    ) -> Result<(), RequestError> {
+        let floors = self.floors();
+        if let Some(floor) = [origin, destination]
+            .into_iter()
+            .find(|floor| *floor >= floors)
+        {
+            return Err(RequestError::FloorOutOfRange { floor, floors });
+        }
+        self.put(origin, destination, weight);
+        Ok(())
+    }
+
+    /// `set` for floors known to be in the building.
+    fn put(&mut self, origin: usize, destination: usize, weight: f64) {
+        if origin != destination {
+            self.weights[origin][destination] = weight;
+        }
+    }
+
+    /// Everyone enters at the lobby and travels up to a floor of their choice.
+    pub fn up_peak(floors: usize) -> Self {
+        let mut od = Self::empty(floors);
+        for destination in 1..floors {
+            od.put(0, destination, 1.0);
+        }
+        od
+    }
+
+    /// Everyone leaves from their floor for the lobby.
+    pub fn down_peak(floors: usize) -> Self {
+        let mut od = Self::empty(floors);
+        for origin in 1..floors {
+            od.put(origin, 0, 1.0);
+        }
+        od
+    }
+
+    /// Trips between any two floors are equally likely.
+    pub fn inter_floor(floors: usize) -> Self {
+        let mut od = Self::empty(floors);
+        for origin in 0..floors {
+            for destination in 0..floors {
+                od.put(origin, destination, 1.0);
+            }
+        }
+        od
+    }
+
+    /// Lunch time: people head down to the lobby and come back up in about
+    /// equal numbers, with some trips between floors on top.
+    pub fn lunch(floors: usize) -> Self {
+        Self::mix(&[
+            (0.4, Self::down_peak(floors)),
+            (0.4, Self::up_peak(floors)),
+            (0.2, Self::inter_floor(floors)),
+        ])
+    }
+
+    /// Blend several matrices, each normalised first and then scaled by its share.
+    pub fn mix(parts: &[(f64, OriginDestination)]) -> Self {
+        let floors = parts.iter().map(|(_, od)| od.floors()).max().unwrap_or(0);
+        let mut mixed = Self::empty(floors);
+
+        for (share, od) in parts {
+            let total = od.total();
+            if total == 0.0 {
+                continue;
+            }
+            for (origin, row) in od.weights.iter().enumerate() {
+                for (destination, weight) in row.iter().enumerate() {
+                    mixed.weights[origin][destination] += share * weight / total;
+                }
+            }
+        }
+        mixed
+    }
+
+    fn total(&self) -> f64 {
+        self.weights.iter().flatten().sum()
+    }
+
+    /// Every trip with some demand, or `None` when there is none at all.
+    fn trips(&self) -> Option<TripSampler> {
+        let (trips, weights): (Vec<_>, Vec<_>) = self
+            .weights
+            .iter()
+            .enumerate()
+            .flat_map(|(origin, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, weight)| **weight > 0.0)
+                    .map(move |(destination, weight)| ((origin, destination), *weight))
+            })
+            .unzip();
+
+        let sampler = WeightedIndex::new(weights).ok()?;
+        Some(TripSampler { trips, sampler })
+    }
+}
+
+/// Picks (origin, destination) pairs in proportion to their demand.
+struct TripSampler {
+    trips: Vec<(usize, usize)>,
+    sampler: WeightedIndex<f64>,
+}
+
+impl TripSampler {
+    fn sample(&self, rng: &mut impl Rng) -> (usize, usize) {
+        self.trips[self.sampler.sample(rng)]
+    }
+}
+
+/// A stretch of the day with a steady arrival rate and trip pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficPeriod {
+    pub duration: Duration,
+    /// Mean number of people arriving per minute.
+    pub arrivals_per_minute: f64,
+    pub demand: OriginDestination,
+}
+
+/// Generates passengers from Poisson arrivals whose rate and trip pattern
+/// change from one period to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficGenerator {
+    pub periods: Vec<TrafficPeriod>,
+}
+
+pub const TRAFFIC_PROFILES: &[&str] =
+    &["up-peak", "lunch", "down-peak", "inter-floor", "office-day"];
+
+/// Look up a traffic profile by name, e.g. from a command line argument.
+///
+/// Every pattern lasts five minutes at its own rate: 20 arrivals a minute at
+/// the up and down peaks, 15 at lunch and 6 between floors the rest of the
+/// day. `office-day` runs the morning, lunch and evening back to back, so
+/// the rate rises and falls with them.
+pub fn traffic_profile(name: &str, floors: usize) -> Option<TrafficGenerator> {
+    let period = |arrivals_per_minute, demand| TrafficPeriod {
+        duration: Duration::from_secs(5 * 60),
+        arrivals_per_minute,
+        demand,
+    };
+    let up_peak = || period(20.0, OriginDestination::up_peak(floors));
+    let lunch = || period(15.0, OriginDestination::lunch(floors));
+    let down_peak = || period(20.0, OriginDestination::down_peak(floors));
+    let inter_floor = || period(6.0, OriginDestination::inter_floor(floors));
+
+    let periods = match name {
+        "up-peak" => vec![up_peak()],
+        "lunch" => vec![lunch()],
+        "down-peak" => vec![down_peak()],
+        "inter-floor" => vec![inter_floor()],
+        "office-day" => vec![
+            up_peak(),
+            inter_floor(),
+            lunch(),
+            inter_floor(),
+            down_peak(),
+        ],
+        _ => return None,
+    };
+
+    Some(TrafficGenerator { periods })
+}
+
+impl TrafficGenerator {
+    pub fn duration(&self) -> Duration {
+        self.periods.iter().map(|period| period.duration).sum()
+    }
+
+    /// Draw the passengers of one run, in arrival order and numbered from 1.
+    pub fn generate(&self, rng: &mut impl Rng) -> Vec<TimedRequest> {
+        let mut requests = Vec::new();
+        let mut period_start = Duration::ZERO;
+
+        for period in &self.periods {
+            let period_end = period_start + period.duration;
+            let rate_per_second = period.arrivals_per_minute / 60.0;
+
+            if let Some(trips) = period.demand.trips().filter(|_| rate_per_second > 0.0) {
+                // Poisson arrivals: exponential gaps. The process has no
+                // memory, so restarting the clock at each period is exact.
+                let mut at = period_start;
+                loop {
+                    let gap = -(1.0 - rng.gen::<f64>()).ln() / rate_per_second;
+                    at += Duration::from_secs_f64(gap);
+                    if at >= period_end {
+                        break;
+                    }
+
+                    let (origin, destination) = trips.sample(rng);
+                    requests.push(TimedRequest {
+                        at,
+                        request: ButtonPressed::new_request(
+                            requests.len() + 1,
+                            origin,
+                            destination,
+                        ),
+                    });
+                }
+            }
+
+            period_start = period_end;
+        }
+
+        requests
+    }
+
+    /// Draw a run and wrap it as a scenario for the given cars.
+    pub fn scenario(&self, elevators: Vec<String>, rng: &mut impl Rng) -> Scenario {
+        let floors = self
+            .periods
+            .iter()
+            .map(|period| period.demand.floors())
+            .max()
+            .unwrap_or(0);
+
+        let requests = self
+            .generate(rng)
+            .into_iter()
+            .map(|timed_request| ScenarioRequest {
+                at_ms: Some(timed_request.at.as_millis() as u64),
+                person_id: timed_request.request.person_id,
+                current_floor: timed_request.request.current_floor,
+                target_floor: timed_request.request.target_floor,
+            })
+            .collect();
+
+        Scenario {
+            floors,
+            elevators,
+            elevator_configs: Default::default(),
+            requests,
+            maintenance: Vec::new(),
+            faults: Vec::new(),
+            fire: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn same_seed_gives_the_same_arrivals() {
+        let traffic = traffic_profile("office-day", 10).unwrap();
+        let run = |seed| traffic.generate(&mut StdRng::seed_from_u64(seed));
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn arrivals_in_each_period_follow_its_rate() {
+        let traffic = traffic_profile("office-day", 10).unwrap();
+        let requests = traffic.generate(&mut StdRng::seed_from_u64(42));
+
+        let mut period_start = Duration::ZERO;
+        for period in &traffic.periods {
+            let period_end = period_start + period.duration;
+            let arrivals = requests
+                .iter()
+                .filter(|r| period_start <= r.at && r.at < period_end)
+                .count() as f64;
+            // Poisson: within four standard deviations of the mean
+            let expected = period.arrivals_per_minute * period.duration.as_secs_f64() / 60.0;
+            assert!(
+                (arrivals - expected).abs() <= 4.0 * expected.sqrt(),
+                "{arrivals} arrivals where {expected} were expected"
+            );
+            period_start = period_end;
+        }
+    }
+
+    #[test]
+    fn demand_is_only_set_between_floors_of_the_building() {
+        let mut od = OriginDestination::empty(3);
+        od.set(0, 2, 1.0).unwrap();
+        assert_eq!(
+            od.set(1, 3, 1.0),
+            Err(RequestError::FloorOutOfRange {
+                floor: 3,
+                floors: 3
+            })
+        );
+        assert_eq!(
+            od.set(5, 0, 1.0),
+            Err(RequestError::FloorOutOfRange {
+                floor: 5,
+                floors: 3
+            })
+        );
+        assert_eq!(od.total(), 1.0);
+    }
+}